@@ -0,0 +1,61 @@
+use std::fs::read_to_string;
+
+use crate::{
+    config::{self, Error},
+    script,
+};
+
+/// Checks the config, the fixture and the scripts without starting the mock
+/// gateway. Prints every problem and returns whether there were none.
+pub fn run() -> bool {
+    let mut errors = 0;
+
+    let config = match config::load("config.json") {
+        Ok(config) => Some(config),
+        Err(err) => {
+            eprintln!("Config Error: {err}\n");
+            errors += 1;
+            None
+        }
+    };
+
+    if let Some(path) = config
+        .as_ref()
+        .and_then(|config| config.mock_data.fixture.as_ref())
+    {
+        if let Err(err) = config::load_fixture(path) {
+            eprintln!("Fixture Error: {err}\n");
+            errors += 1;
+        }
+    }
+
+    // `script.txt` is optional, the scripts in the config are not
+    let scripts = config
+        .iter()
+        .flat_map(|config| &config.scripts)
+        .map(|script| (script.path.as_str(), true));
+
+    for (path, required) in [("script.txt", false)].into_iter().chain(scripts) {
+        match read_to_string(path) {
+            Ok(content) => {
+                for diagnostic in script::check(path, &content) {
+                    eprintln!("{diagnostic}\n");
+                    errors += 1;
+                }
+            }
+            Err(_) if !required => {}
+            Err(_) => {
+                eprintln!("Script Error: {}\n", Error::NotFound(path.to_string()));
+                errors += 1;
+            }
+        }
+    }
+
+    if errors == 0 {
+        println!("No problems found");
+    } else {
+        eprintln!("Found {errors} problems");
+    }
+
+    errors == 0
+}
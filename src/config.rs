@@ -1,24 +1,28 @@
 use std::{
     fmt::{Display, Formatter, Result as FmtResult},
-    fs::read_to_string,
+    fs::{metadata, read_to_string},
+    iter,
     process::exit,
-    sync::LazyLock,
+    sync::{Arc, LazyLock, RwLock},
+    time::SystemTime,
 };
 
 use serde::Deserialize;
 use simd_json::Error as JsonError;
+use tracing::{error, info};
 use twilight_model::{
-    gateway::Intents,
+    gateway::{Intents, ShardId},
+    guild::Guild,
     id::{
         marker::{ApplicationMarker, UserMarker},
         Id,
     },
     oauth::{ApplicationFlags, PartialApplication},
-    user::{CurrentUser, UserFlags},
+    user::{CurrentUser, User, UserFlags},
     util::ImageHash,
 };
 
-use crate::script::{self, Action};
+use crate::script::{self, Action, ScriptError};
 
 #[derive(Deserialize)]
 pub struct Config {
@@ -28,6 +32,13 @@ pub struct Config {
     pub scenarios: Scenarios,
     pub bot: Bot,
     pub mock_data: MockData,
+    /// Scripts that shards run instead of `script.txt`.
+    #[serde(default)]
+    pub scripts: Vec<ShardScript>,
+    /// Rejects scripts with dispatch payloads that twilight-model cannot
+    /// deserialize as their event.
+    #[serde(default)]
+    pub strict_dispatches: bool,
 }
 
 #[derive(Deserialize)]
@@ -67,6 +78,31 @@ impl Into<CurrentUser> for &Bot {
     }
 }
 
+impl From<&Bot> for User {
+    fn from(bot: &Bot) -> Self {
+        Self {
+            accent_color: None,
+            avatar: bot
+                .avatar
+                .as_ref()
+                .and_then(|avatar| ImageHash::parse(avatar.as_bytes()).ok()),
+            banner: None,
+            bot: true,
+            discriminator: bot.discriminator,
+            email: None,
+            flags: None,
+            id: bot.user_id,
+            locale: None,
+            mfa_enabled: None,
+            name: bot.name.clone(),
+            premium_type: None,
+            public_flags: bot.public_flags,
+            system: None,
+            verified: None,
+        }
+    }
+}
+
 impl Into<PartialApplication> for &Bot {
     fn into(self) -> PartialApplication {
         PartialApplication {
@@ -115,14 +151,58 @@ pub struct Scenarios {
 
 #[derive(Deserialize)]
 pub struct MockData {
-    guilds: u32,
-    users: u32,
-    channels: u32,
-    voice_states: u32,
+    /// Path to a JSON file with a fixed world to serve instead of generating
+    /// one. The generator options below are ignored if set.
+    pub fixture: Option<String>,
+    /// Seed for the world generator. The same seed always generates the same
+    /// world, a random one is picked and logged if unset.
+    pub seed: Option<u64>,
+    /// Amount of guilds to generate.
+    #[serde(default)]
+    pub guilds: u32,
+    /// Size of the user pool that guild members are drawn from.
+    #[serde(default)]
+    pub users: u32,
+    /// Amount of channels to generate per guild.
+    #[serde(default)]
+    pub channels: u32,
+    /// Amount of voice states to generate per guild.
+    #[serde(default)]
+    pub voice_states: u32,
+    /// Amount of roles to generate per guild, in addition to `@everyone` and
+    /// the managed roles of bots.
+    #[serde(default)]
+    pub roles: u32,
+    /// Amount of custom emojis to generate per guild.
+    #[serde(default)]
+    pub emojis: u32,
+    /// Amount of stickers to generate per guild.
+    #[serde(default)]
+    pub stickers: u32,
+}
+
+/// A script for a range of shards. Clients without shards count as shard 0.
+#[derive(Deserialize)]
+pub struct ShardScript {
+    /// First shard that runs the script.
+    pub first_shard: u64,
+    /// Last shard that runs the script.
+    pub last_shard: u64,
+    /// Path to the script file.
+    pub path: String,
+}
+
+/// A hand-written world, in the same shape as the guilds sent in
+/// GUILD_CREATE.
+#[derive(Deserialize)]
+pub struct Fixture {
+    pub guilds: Vec<Guild>,
 }
 
 pub enum Error {
     InvalidConfig(JsonError),
+    InvalidFixture(JsonError),
+    InvalidScript(Box<ScriptError>),
     NotFound(String),
 }
 
@@ -130,6 +210,8 @@ impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match self {
             Self::InvalidConfig(s) => s.fmt(f),
+            Self::InvalidFixture(s) => s.fmt(f),
+            Self::InvalidScript(s) => s.fmt(f),
             Self::NotFound(s) => f.write_fmt(format_args!("File {s} not found or access denied")),
         }
     }
@@ -142,6 +224,13 @@ pub fn load(path: &str) -> Result<Config, Error> {
     Ok(config)
 }
 
+pub fn load_fixture(path: &str) -> Result<Fixture, Error> {
+    let mut content = read_to_string(path).map_err(|_| Error::NotFound(path.to_string()))?;
+    let fixture = unsafe { simd_json::from_str(&mut content) }.map_err(Error::InvalidFixture)?;
+
+    Ok(fixture)
+}
+
 pub static CONFIG: LazyLock<Config> = LazyLock::new(|| {
     match load("config.json") {
         Ok(config) => config,
@@ -153,14 +242,88 @@ pub static CONFIG: LazyLock<Config> = LazyLock::new(|| {
     }
 });
 
-pub static SCRIPT: LazyLock<Vec<Action>> = LazyLock::new(|| {
-    let script_content = read_to_string("script.txt").unwrap_or_default();
+/// `script.txt` and the scripts of `CONFIG.scripts`, in the same order.
+/// Running scripts hold on to the script they started with.
+struct LoadedScripts {
+    default: Arc<[Action]>,
+    shards: Vec<Arc<[Action]>>,
+}
 
-    match script::parse(&script_content) {
-        Ok(script) => script,
-        Err(err) => {
-            eprintln!("Failed to parse script: {err}");
-            exit(1);
-        }
+fn load_scripts() -> Result<LoadedScripts, Error> {
+    // Without a script, clients are only served the initial events
+    let default = match read_to_string("script.txt") {
+        Ok(content) => script::parse("script.txt", &content, CONFIG.strict_dispatches)
+            .map_err(Error::InvalidScript)?
+            .into(),
+        Err(_) => Arc::from([]),
+    };
+
+    let shards = CONFIG
+        .scripts
+        .iter()
+        .map(|script| {
+            let content =
+                read_to_string(&script.path).map_err(|_| Error::NotFound(script.path.clone()))?;
+
+            script::parse(&script.path, &content, CONFIG.strict_dispatches)
+                .map(Arc::from)
+                .map_err(Error::InvalidScript)
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(LoadedScripts { default, shards })
+}
+
+/// The current scripts.
+static SCRIPTS: LazyLock<RwLock<LoadedScripts>> = LazyLock::new(|| match load_scripts() {
+    Ok(scripts) => RwLock::new(scripts),
+    Err(err @ Error::InvalidScript(_)) => {
+        eprintln!("{err}");
+        exit(1);
+    }
+    Err(err) => {
+        eprintln!("Script Error: {err}");
+        exit(1);
     }
 });
+
+/// Parses the scripts, so that invalid scripts stop the mock at startup
+/// instead of when the first client identifies.
+pub fn load_scripts_now() {
+    LazyLock::force(&SCRIPTS);
+}
+
+/// Parses the scripts again, for connections that become ready afterwards.
+/// The current scripts are kept if the new ones are invalid.
+pub fn reload_scripts() {
+    match load_scripts() {
+        Ok(scripts) => {
+            *SCRIPTS.write().expect("Scripts lock poisoned") = scripts;
+            info!("Reloaded scripts");
+        }
+        Err(err) => error!("Keeping the current scripts because reloading failed:\n{err}"),
+    }
+}
+
+/// When the script files were last modified, to notice changes.
+pub fn scripts_modified() -> Vec<Option<SystemTime>> {
+    iter::once("script.txt")
+        .chain(CONFIG.scripts.iter().map(|script| script.path.as_str()))
+        .map(|path| metadata(path).and_then(|metadata| metadata.modified()).ok())
+        .collect()
+}
+
+/// The script for the shard, from the first range in `CONFIG.scripts` that
+/// contains it, or `script.txt` otherwise.
+pub fn script_for(shard: Option<ShardId>) -> Arc<[Action]> {
+    let number = shard.map_or(0, ShardId::number);
+    let scripts = SCRIPTS.read().expect("Scripts lock poisoned");
+
+    CONFIG
+        .scripts
+        .iter()
+        .zip(&scripts.shards)
+        .find(|(script, _)| (script.first_shard..=script.last_shard).contains(&number))
+        .map_or(&scripts.default, |(_, actions)| actions)
+        .clone()
+}
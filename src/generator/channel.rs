@@ -0,0 +1,327 @@
+use rand::{seq::index::sample, Rng};
+use twilight_model::{
+    channel::{
+        forum::{DefaultReaction, ForumLayout, ForumSortOrder, ForumTag},
+        permission_overwrite::{PermissionOverwrite, PermissionOverwriteType},
+        thread::AutoArchiveDuration,
+        Channel, ChannelFlags, ChannelType, VideoQualityMode,
+    },
+    guild::{Member, Permissions, Role},
+    id::{
+        marker::{ChannelMarker, GenericMarker, GuildMarker},
+        Id,
+    },
+};
+
+use super::{created_at, Generator};
+
+/// Media channels are not known to twilight-model yet.
+const GUILD_MEDIA: ChannelType = ChannelType::Unknown(16);
+
+/// The first channels of every guild, so that each channel type is covered
+/// as soon as there are enough channels.
+const GUARANTEED_KINDS: &[ChannelType] = &[
+    ChannelType::GuildText,
+    ChannelType::GuildVoice,
+    ChannelType::GuildAnnouncement,
+    ChannelType::GuildStageVoice,
+    ChannelType::GuildForum,
+    GUILD_MEDIA,
+];
+
+/// Kinds of the remaining channels, weighted by how common they are.
+const WEIGHTED_KINDS: &[(ChannelType, u32)] = &[
+    (ChannelType::GuildText, 50),
+    (ChannelType::GuildVoice, 20),
+    (ChannelType::GuildAnnouncement, 8),
+    (ChannelType::GuildStageVoice, 4),
+    (ChannelType::GuildForum, 12),
+    (GUILD_MEDIA, 6),
+];
+
+const SLOWMODES: &[u16] = &[0, 0, 0, 0, 5, 10, 30, 60, 300, 21600];
+const BITRATES: &[u32] = &[8000, 64000, 64000, 96000, 128000];
+const USER_LIMITS: &[u32] = &[0, 0, 0, 2, 5, 10, 25, 99];
+
+/// Permissions that can be overwritten on a channel level.
+const CHANNEL_PERMISSIONS: Permissions = Permissions::all()
+    .difference(Permissions::ADMINISTRATOR)
+    .difference(Permissions::KICK_MEMBERS)
+    .difference(Permissions::BAN_MEMBERS)
+    .difference(Permissions::MANAGE_GUILD)
+    .difference(Permissions::VIEW_AUDIT_LOG)
+    .difference(Permissions::VIEW_GUILD_INSIGHTS)
+    .difference(Permissions::CHANGE_NICKNAME)
+    .difference(Permissions::MANAGE_NICKNAMES)
+    .difference(Permissions::MANAGE_EMOJIS_AND_STICKERS)
+    .difference(Permissions::MODERATE_MEMBERS);
+
+/// A channel with only the fields that every channel type has set.
+fn base_channel(id: Id<ChannelMarker>, guild_id: Id<GuildMarker>, kind: ChannelType) -> Channel {
+    Channel {
+        application_id: None,
+        applied_tags: None,
+        available_tags: None,
+        bitrate: None,
+        default_auto_archive_duration: None,
+        default_forum_layout: None,
+        default_reaction_emoji: None,
+        default_sort_order: None,
+        default_thread_rate_limit_per_user: None,
+        flags: None,
+        guild_id: Some(guild_id),
+        icon: None,
+        id,
+        invitable: None,
+        kind,
+        last_message_id: None,
+        last_pin_timestamp: None,
+        member: None,
+        member_count: None,
+        message_count: None,
+        name: None,
+        newly_created: None,
+        nsfw: None,
+        owner_id: None,
+        parent_id: None,
+        permission_overwrites: Some(Vec::new()),
+        position: None,
+        rate_limit_per_user: None,
+        recipients: None,
+        rtc_region: None,
+        thread_metadata: None,
+        topic: None,
+        user_limit: None,
+        video_quality_mode: None,
+    }
+}
+
+/// Whether the channel type is sorted among the voice channels in the client.
+fn is_voice(kind: ChannelType) -> bool {
+    matches!(kind, ChannelType::GuildVoice | ChannelType::GuildStageVoice)
+}
+
+impl Generator {
+    /// Generates `count` channels, roughly one in six of them categories. The
+    /// permission overwrites reference the given roles and members, and the
+    /// guild's ID for `@everyone`.
+    pub(super) fn channels(
+        &mut self,
+        guild_id: Id<GuildMarker>,
+        count: u32,
+        roles: &[Role],
+        members: &[Member],
+    ) -> Vec<Channel> {
+        let count = count as usize;
+        let category_count = if count >= 3 { (count / 6).max(1) } else { 0 };
+
+        let mut channels: Vec<Channel> = (0..category_count)
+            .map(|position| {
+                let id = self.snowflake(created_at(guild_id));
+                let mut category = base_channel(id, guild_id, ChannelType::GuildCategory);
+                category.name = Some(self.category_name());
+                category.position = Some(position as i32);
+                category.permission_overwrites =
+                    Some(self.permission_overwrites(guild_id, roles, members));
+
+                category
+            })
+            .collect();
+
+        let mut text_position = 0;
+        let mut voice_position = 0;
+
+        for i in 0..count - category_count {
+            let kind = match GUARANTEED_KINDS.get(i) {
+                Some(kind) => *kind,
+                None => self.weighted_kind(),
+            };
+
+            let position = if is_voice(kind) {
+                &mut voice_position
+            } else {
+                &mut text_position
+            };
+
+            let mut channel = self.channel(guild_id, kind);
+            channel.position = Some(*position);
+            *position += 1;
+
+            // Most channels are in a category and many of these have their
+            // permissions synced with it
+            if category_count > 0 && self.rng.gen_bool(0.85) {
+                let category = &channels[self.rng.gen_range(0..category_count)];
+                channel.parent_id = Some(category.id);
+
+                if self.rng.gen_bool(0.6) {
+                    channel.permission_overwrites = category.permission_overwrites.clone();
+                } else {
+                    channel.permission_overwrites =
+                        Some(self.permission_overwrites(guild_id, roles, members));
+                }
+            } else {
+                channel.permission_overwrites =
+                    Some(self.permission_overwrites(guild_id, roles, members));
+            }
+
+            channels.push(channel);
+        }
+
+        channels
+    }
+
+    fn weighted_kind(&mut self) -> ChannelType {
+        let total: u32 = WEIGHTED_KINDS.iter().map(|(_, weight)| weight).sum();
+        let mut roll = self.rng.gen_range(0..total);
+
+        for (kind, weight) in WEIGHTED_KINDS {
+            if roll < *weight {
+                return *kind;
+            }
+
+            roll -= weight;
+        }
+
+        unreachable!()
+    }
+
+    /// Generates a non-category channel of the given type without parent,
+    /// position or permission overwrites.
+    fn channel(&mut self, guild_id: Id<GuildMarker>, kind: ChannelType) -> Channel {
+        let id = self.snowflake(created_at(guild_id));
+        let mut channel = base_channel(id, guild_id, kind);
+        channel.name = Some(self.channel_name());
+        channel.nsfw = Some(self.rng.gen_bool(0.1));
+
+        match kind {
+            ChannelType::GuildText | ChannelType::GuildAnnouncement => {
+                channel.topic = self.rng.gen_bool(0.6).then(|| self.sentence(3..20));
+                channel.default_auto_archive_duration = Some(AutoArchiveDuration::Day);
+
+                // Announcement channels cannot have a slowmode
+                if kind == ChannelType::GuildText {
+                    channel.rate_limit_per_user = Some(*self.pick(SLOWMODES));
+                }
+            }
+            ChannelType::GuildVoice | ChannelType::GuildStageVoice => {
+                channel.bitrate = Some(*self.pick(BITRATES));
+                channel.rate_limit_per_user = Some(0);
+
+                if kind == ChannelType::GuildVoice {
+                    channel.user_limit = Some(*self.pick(USER_LIMITS));
+                    channel.video_quality_mode = Some(VideoQualityMode::Auto);
+                } else {
+                    channel.topic = self.rng.gen_bool(0.3).then(|| self.sentence(2..8));
+                    channel.user_limit = Some(10000);
+                }
+            }
+            _ => {
+                // Forum and media channels
+                channel.topic = self.rng.gen_bool(0.8).then(|| self.sentence(5..30));
+                channel.rate_limit_per_user = Some(*self.pick(SLOWMODES));
+                channel.default_thread_rate_limit_per_user = Some(*self.pick(SLOWMODES));
+                channel.default_auto_archive_duration = Some(AutoArchiveDuration::ThreeDays);
+                channel.available_tags = Some(self.forum_tags(created_at(id)));
+                channel.default_reaction_emoji = self.rng.gen_bool(0.5).then(|| DefaultReaction {
+                    emoji_id: None,
+                    emoji_name: Some(self.emoji()),
+                });
+                channel.default_sort_order = Some(if self.rng.gen_bool(0.5) {
+                    ForumSortOrder::LatestActivity
+                } else {
+                    ForumSortOrder::CreationDate
+                });
+                channel.flags = Some(if self.rng.gen_bool(0.3) {
+                    ChannelFlags::REQUIRE_TAG
+                } else {
+                    ChannelFlags::empty()
+                });
+
+                if kind == ChannelType::GuildForum {
+                    channel.default_forum_layout = Some(if self.rng.gen_bool(0.5) {
+                        ForumLayout::ListView
+                    } else {
+                        ForumLayout::GalleryView
+                    });
+                }
+            }
+        }
+
+        channel
+    }
+
+    fn forum_tags(&mut self, not_before: u64) -> Vec<ForumTag> {
+        let count = self.count(0..6);
+
+        (0..count)
+            .map(|_| ForumTag {
+                emoji_id: None,
+                emoji_name: self.rng.gen_bool(0.5).then(|| self.emoji()),
+                id: self.snowflake(not_before),
+                moderated: self.rng.gen_bool(0.2),
+                name: self.tag_name(),
+            })
+            .collect()
+    }
+
+    fn permission_overwrites(
+        &mut self,
+        guild_id: Id<GuildMarker>,
+        roles: &[Role],
+        members: &[Member],
+    ) -> Vec<PermissionOverwrite> {
+        let mut overwrites = Vec::new();
+
+        if self.rng.gen_bool(0.3) {
+            // Hide the channel from @everyone or make it read-only
+            let deny = if self.rng.gen_bool(0.5) {
+                Permissions::VIEW_CHANNEL
+            } else {
+                Permissions::SEND_MESSAGES
+            };
+
+            overwrites.push(PermissionOverwrite {
+                allow: Permissions::empty(),
+                deny,
+                id: guild_id.cast(),
+                kind: PermissionOverwriteType::Role,
+            });
+        }
+
+        // The @everyone role is covered above
+        let roles: Vec<&Role> = roles
+            .iter()
+            .filter(|role| role.id != guild_id.cast())
+            .collect();
+
+        let role_count = self.count(0..3).min(roles.len());
+
+        for idx in sample(&mut self.rng, roles.len(), role_count) {
+            let id = roles[idx].id.cast();
+            overwrites.push(self.permission_overwrite(id, PermissionOverwriteType::Role));
+        }
+
+        if !members.is_empty() && self.rng.gen_bool(0.2) {
+            let id = self.pick(members).user.id.cast();
+            overwrites.push(self.permission_overwrite(id, PermissionOverwriteType::Member));
+        }
+
+        overwrites
+    }
+
+    fn permission_overwrite(
+        &mut self,
+        id: Id<GenericMarker>,
+        kind: PermissionOverwriteType,
+    ) -> PermissionOverwrite {
+        let allow = self.permissions(CHANNEL_PERMISSIONS);
+        let deny = self.permissions(CHANNEL_PERMISSIONS.difference(allow));
+
+        PermissionOverwrite {
+            allow,
+            deny,
+            id,
+            kind,
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use rand::{seq::index::sample, Rng};
+use twilight_model::{
+    guild::{Emoji, Role},
+    id::{marker::GuildMarker, Id},
+};
+
+use super::{created_at, Generator};
+
+impl Generator {
+    /// Generates custom emojis, some of which are restricted to one of the
+    /// given roles.
+    pub(super) fn emojis(
+        &mut self,
+        guild_id: Id<GuildMarker>,
+        count: u32,
+        roles: &[Role],
+    ) -> Vec<Emoji> {
+        (0..count)
+            .map(|_| {
+                let id = self.snowflake(created_at(guild_id));
+
+                // @everyone is always the first role
+                let restricted_roles = if roles.len() > 1 && self.rng.gen_bool(0.1) {
+                    let amount = self.count(1..3).min(roles.len() - 1);
+                    sample(&mut self.rng, roles.len() - 1, amount)
+                        .into_iter()
+                        .map(|idx| roles[idx + 1].id)
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+
+                Emoji {
+                    animated: self.rng.gen_bool(0.2),
+                    available: self.rng.gen_bool(0.95),
+                    id,
+                    managed: false,
+                    name: self.emoji_name(),
+                    require_colons: true,
+                    roles: restricted_roles,
+                    user: None, // Omitted in GUILD_CREATE
+                }
+            })
+            .collect()
+    }
+}
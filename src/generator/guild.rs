@@ -0,0 +1,131 @@
+use rand::{seq::index::sample, Rng};
+use twilight_model::{
+    channel::ChannelType,
+    guild::{
+        AfkTimeout, DefaultMessageNotificationLevel, ExplicitContentFilter, Guild, GuildFeature,
+        Member, MfaLevel, NSFWLevel, PremiumTier, SystemChannelFlags, VerificationLevel,
+    },
+};
+
+use super::{Generator, UserPool, GENERATION_START};
+use crate::config::Config;
+
+impl Generator {
+    /// Generates a guild with members drawn from the user pool.
+    pub(super) fn guild(&mut self, pool: &UserPool, config: &Config) -> Guild {
+        let users = &pool.users;
+        let id = self.snowflake(GENERATION_START);
+        let name = self.guild_name();
+        let icon = self.rng.gen_bool(0.7).then(|| self.image_hash());
+
+        // Every guild has more than half of the user pool as members, so that
+        // all guilds share some of their members
+        let member_count = if users.is_empty() {
+            0
+        } else {
+            self.count(users.len() / 2 + 1..users.len() + 1)
+        };
+        let member_indices = sample(&mut self.rng, users.len(), member_count).into_vec();
+        let mut members: Vec<Member> = member_indices
+            .iter()
+            .map(|idx| self.member(&users[*idx], id))
+            .collect();
+        let presences = member_indices
+            .iter()
+            .filter_map(|idx| {
+                pool.presences[*idx]
+                    .as_ref()
+                    .map(|presence| presence.for_guild(users[*idx].id, id))
+            })
+            .collect();
+
+        let owner_id = if members.is_empty() {
+            config.bot.user_id
+        } else {
+            self.pick(&members).user.id
+        };
+
+        let mut bot_member = self.member(&(&config.bot).into(), id);
+        bot_member.communication_disabled_until = None;
+        bot_member.pending = false;
+        let joined_at = bot_member.joined_at;
+        members.push(bot_member);
+
+        let roles = self.roles(id, config.mock_data.roles, &members);
+        self.assign_roles(&mut members, &roles);
+        let channels = self.channels(id, config.mock_data.channels, &roles, &members);
+        let emojis = self.emojis(id, config.mock_data.emojis, &roles);
+        let stickers = self.stickers(id, config.mock_data.stickers);
+        let voice_states =
+            self.voice_states(&members, &channels, config.mock_data.voice_states as usize);
+
+        let system_channel_id = channels
+            .iter()
+            .find(|channel| channel.kind == ChannelType::GuildText)
+            .map(|channel| channel.id);
+
+        let mut features = Vec::new();
+
+        if roles
+            .iter()
+            .any(|role| role.icon.is_some() || role.unicode_emoji.is_some())
+        {
+            features.push(GuildFeature::RoleIcons);
+        }
+
+        if stickers.len() > 5 {
+            features.push(GuildFeature::MoreStickers);
+        }
+
+        Guild {
+            afk_channel_id: None,
+            afk_timeout: AfkTimeout::FIVE_MINUTES,
+            application_id: None,
+            approximate_member_count: None,
+            approximate_presence_count: None,
+            banner: None,
+            channels,
+            default_message_notifications: DefaultMessageNotificationLevel::Mentions,
+            description: None,
+            discovery_splash: None,
+            emojis,
+            explicit_content_filter: ExplicitContentFilter::MembersWithoutRole,
+            features,
+            icon,
+            id,
+            joined_at: Some(joined_at),
+            large: false,
+            max_members: Some(500_000),
+            max_presences: None,
+            max_video_channel_users: Some(25),
+            member_count: Some(members.len() as u64),
+            members,
+            mfa_level: MfaLevel::None,
+            name,
+            nsfw_level: NSFWLevel::Default,
+            owner_id,
+            owner: None,
+            permissions: None,
+            preferred_locale: String::from("en-US"),
+            premium_progress_bar_enabled: false,
+            premium_subscription_count: Some(0),
+            premium_tier: PremiumTier::None,
+            presences,
+            public_updates_channel_id: None,
+            roles,
+            rules_channel_id: None,
+            splash: None,
+            stage_instances: Vec::new(),
+            stickers,
+            system_channel_flags: SystemChannelFlags::empty(),
+            system_channel_id,
+            threads: Vec::new(),
+            unavailable: false,
+            vanity_url_code: None,
+            verification_level: VerificationLevel::Low,
+            voice_states,
+            widget_channel_id: None,
+            widget_enabled: None,
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use rand::{seq::index::sample, Rng};
+use twilight_model::{
+    guild::{Member, MemberFlags, Role},
+    id::{marker::GuildMarker, Id},
+    user::User,
+};
+
+use super::{created_at, now, timestamp, Generator};
+
+/// Longest possible timeout in milliseconds.
+const MAX_TIMEOUT: u64 = 28 * 24 * 60 * 60 * 1000;
+
+impl Generator {
+    pub(super) fn member(&mut self, user: &User, guild_id: Id<GuildMarker>) -> Member {
+        // Users can only join guilds that exist after they registered
+        let joined_at = self.time_after(created_at(user.id).max(created_at(guild_id)));
+
+        let nick = self.rng.gen_bool(0.3).then(|| self.username());
+        let avatar = self.rng.gen_bool(0.1).then(|| self.image_hash());
+        let premium_since = self
+            .rng
+            .gen_bool(0.05)
+            .then(|| timestamp(self.time_after(joined_at)));
+
+        // Timeouts have to be in the future to be active, so unlike everything
+        // else they are relative to the current time
+        let communication_disabled_until = self
+            .rng
+            .gen_bool(0.03)
+            .then(|| timestamp(now() + self.rng.gen_range(60_000..MAX_TIMEOUT)));
+
+        let pending = self.rng.gen_bool(0.05);
+        let mut flags = MemberFlags::empty();
+
+        if self.rng.gen_bool(0.1) {
+            flags |= MemberFlags::DID_REJOIN;
+        }
+
+        if !pending {
+            flags |= MemberFlags::COMPLETED_ONBOARDING;
+        }
+
+        Member {
+            avatar,
+            communication_disabled_until,
+            deaf: self.rng.gen_bool(0.01),
+            flags,
+            joined_at: timestamp(joined_at),
+            mute: self.rng.gen_bool(0.01),
+            nick,
+            pending,
+            premium_since,
+            roles: Vec::new(),
+            user: user.clone(),
+        }
+    }
+
+    /// Assigns random roles to the members. Managed roles are only assigned
+    /// to the bot they belong to or to boosters.
+    pub(super) fn assign_roles(&mut self, members: &mut [Member], roles: &[Role]) {
+        // @everyone is implicit and never part of the member's roles
+        let assignable: Vec<&Role> = roles.iter().skip(1).filter(|role| !role.managed).collect();
+
+        for member in members {
+            // Pending members did not pass membership screening yet and
+            // cannot have roles
+            if !member.pending {
+                let count = self.count(0..5).min(assignable.len());
+
+                member.roles = sample(&mut self.rng, assignable.len(), count)
+                    .into_iter()
+                    .map(|idx| assignable[idx].id)
+                    .collect();
+            }
+
+            for role in roles.iter().filter(|role| role.managed) {
+                let Some(tags) = &role.tags else {
+                    continue;
+                };
+
+                if tags.bot_id == Some(member.user.id)
+                    || (tags.premium_subscriber && member.premium_since.is_some())
+                {
+                    member.roles.push(role.id);
+                }
+            }
+        }
+    }
+}
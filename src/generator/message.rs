@@ -0,0 +1,298 @@
+use std::collections::{HashMap, VecDeque};
+
+use rand::Rng;
+use twilight_model::{
+    channel::{
+        message::{
+            embed::{EmbedField, EmbedFooter},
+            Embed, Mention, MessageFlags, MessageReference, MessageType,
+        },
+        Attachment, ChannelType, Message,
+    },
+    guild::{Guild, Member, PartialMember},
+    id::{
+        marker::{ChannelMarker, RoleMarker},
+        Id,
+    },
+    user::UserFlags,
+};
+
+use super::{now, timestamp, Generator};
+
+const ATTACHMENT_KINDS: &[(&str, &str)] = &[
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("mp4", "video/mp4"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("zip", "application/zip"),
+];
+
+const IMAGE_SIZES: &[u64] = &[128, 256, 512, 720, 1080, 1920];
+
+/// Whether members can send messages in the channel type.
+fn is_text(kind: ChannelType) -> bool {
+    matches!(
+        kind,
+        ChannelType::GuildText | ChannelType::GuildAnnouncement
+    )
+}
+
+/// The member as it is embedded in messages, without the user.
+fn partial_member(member: &Member) -> PartialMember {
+    PartialMember {
+        avatar: member.avatar,
+        communication_disabled_until: member.communication_disabled_until,
+        deaf: member.deaf,
+        flags: member.flags,
+        joined_at: member.joined_at,
+        mute: member.mute,
+        nick: member.nick.clone(),
+        permissions: None,
+        premium_since: member.premium_since,
+        roles: member.roles.clone(),
+        user: None,
+    }
+}
+
+fn mention(member: &Member) -> Mention {
+    Mention {
+        avatar: member.user.avatar,
+        bot: member.user.bot,
+        discriminator: member.user.discriminator,
+        id: member.user.id,
+        member: Some(partial_member(member)),
+        name: member.user.name.clone(),
+        public_flags: member.user.public_flags.unwrap_or(UserFlags::empty()),
+    }
+}
+
+impl Generator {
+    /// Generates a message by a member in a text channel of one of the guilds,
+    /// or `None` if none of them has a text channel. Replies reference a
+    /// message from the channel's history.
+    pub fn message(
+        &mut self,
+        guilds: &[&Guild],
+        history: &HashMap<Id<ChannelMarker>, VecDeque<Message>>,
+    ) -> Option<Message> {
+        let guilds: Vec<&Guild> = guilds
+            .iter()
+            .copied()
+            .filter(|guild| {
+                !guild.members.is_empty()
+                    && guild.channels.iter().any(|channel| is_text(channel.kind))
+            })
+            .collect();
+
+        if guilds.is_empty() {
+            return None;
+        }
+
+        let guild = *self.pick(&guilds);
+        let channels: Vec<Id<ChannelMarker>> = guild
+            .channels
+            .iter()
+            .filter(|channel| is_text(channel.kind))
+            .map(|channel| channel.id)
+            .collect();
+        let channel_id = *self.pick(&channels);
+        let author = self.pick(&guild.members);
+
+        let created_at = now();
+        let id = self.snowflake_at(created_at);
+
+        let mut content = self.sentence(1..20);
+        let mut mentions = Vec::new();
+        let mut mention_roles: Vec<Id<RoleMarker>> = Vec::new();
+
+        for _ in 0..self.count(0..3) {
+            match self.rng.gen_range(0..4) {
+                0 | 1 => {
+                    let member = self.pick(&guild.members);
+                    content.push_str(&format!(" <@{}>", member.user.id));
+
+                    if !mentions
+                        .iter()
+                        .any(|mention: &Mention| mention.id == member.user.id)
+                    {
+                        mentions.push(mention(member));
+                    }
+                }
+                2 => {
+                    // Skip @everyone, which is mentioned differently
+                    let roles: Vec<Id<RoleMarker>> = guild
+                        .roles
+                        .iter()
+                        .skip(1)
+                        .filter(|role| role.mentionable && !mention_roles.contains(&role.id))
+                        .map(|role| role.id)
+                        .collect();
+
+                    if roles.is_empty() {
+                        continue;
+                    }
+
+                    let role_id = *self.pick(&roles);
+                    content.push_str(&format!(" <@&{role_id}>"));
+                    mention_roles.push(role_id);
+                }
+                _ => {
+                    let channel_id = *self.pick(&channels);
+                    content.push_str(&format!(" <#{channel_id}>"));
+                }
+            }
+        }
+
+        let mention_everyone = self.rng.gen_bool(0.02);
+
+        if mention_everyone {
+            content.insert_str(0, "@everyone ");
+        }
+
+        let mut kind = MessageType::Regular;
+        let mut reference = None;
+        let mut referenced_message = None;
+
+        // Drawn even without history, so that messages of other connections
+        // don't shift the draws of later messages
+        let reply = self.rng.gen_bool(0.2);
+        let reply_index: usize = self.rng.gen();
+        let ping = self.rng.gen_bool(0.5);
+
+        if let Some(messages) = history.get(&channel_id).filter(|m| !m.is_empty()) {
+            if reply {
+                let replied_to = &messages[reply_index % messages.len()];
+
+                kind = MessageType::Reply;
+                reference = Some(MessageReference {
+                    channel_id: Some(channel_id),
+                    guild_id: Some(guild.id),
+                    message_id: Some(replied_to.id),
+                    fail_if_not_exists: None,
+                });
+
+                // Replies ping the author of the referenced message by default
+                if let Some(member) = guild
+                    .members
+                    .iter()
+                    .find(|member| member.user.id == replied_to.author.id)
+                    .filter(|_| ping)
+                {
+                    if !mentions.iter().any(|mention| mention.id == member.user.id) {
+                        mentions.push(mention(member));
+                    }
+                }
+
+                // Referenced messages do not reference further messages
+                let mut replied_to = replied_to.clone();
+                replied_to.referenced_message = None;
+                referenced_message = Some(Box::new(replied_to));
+            }
+        }
+
+        let embeds = if self.rng.gen_bool(0.15) {
+            vec![self.embed(created_at)]
+        } else {
+            Vec::new()
+        };
+
+        let attachments = if self.rng.gen_bool(0.1) {
+            (0..self.count(1..4))
+                .map(|_| self.attachment(channel_id, created_at))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Some(Message {
+            activity: None,
+            application: None,
+            application_id: None,
+            attachments,
+            author: author.user.clone(),
+            channel_id,
+            components: Vec::new(),
+            content,
+            edited_timestamp: None,
+            embeds,
+            flags: Some(MessageFlags::empty()),
+            guild_id: Some(guild.id),
+            id,
+            interaction: None,
+            kind,
+            member: Some(partial_member(author)),
+            mention_channels: Vec::new(),
+            mention_everyone,
+            mention_roles,
+            mentions,
+            pinned: false,
+            reactions: Vec::new(),
+            reference,
+            referenced_message,
+            role_subscription_data: None,
+            sticker_items: Vec::new(),
+            timestamp: timestamp(created_at),
+            thread: None,
+            tts: false,
+            webhook_id: None,
+        })
+    }
+
+    fn embed(&mut self, created_at: u64) -> Embed {
+        let fields = (0..self.count(0..4))
+            .map(|_| EmbedField {
+                inline: self.rng.gen_bool(0.5),
+                name: self.sentence(1..4),
+                value: self.sentence(2..12),
+            })
+            .collect();
+
+        Embed {
+            author: None,
+            color: Some(self.rng.gen_range(0..=0xFFFFFF)),
+            description: self.rng.gen_bool(0.8).then(|| self.sentence(5..40)),
+            fields,
+            footer: self.rng.gen_bool(0.3).then(|| EmbedFooter {
+                icon_url: None,
+                proxy_icon_url: None,
+                text: self.sentence(1..6),
+            }),
+            image: None,
+            kind: String::from("rich"),
+            provider: None,
+            thumbnail: None,
+            timestamp: self.rng.gen_bool(0.3).then(|| timestamp(created_at)),
+            title: Some(self.sentence(1..8)),
+            url: None,
+            video: None,
+        }
+    }
+
+    fn attachment(&mut self, channel_id: Id<ChannelMarker>, created_at: u64) -> Attachment {
+        let id = self.snowflake_at(created_at);
+        let (extension, content_type) = *self.pick(ATTACHMENT_KINDS);
+        let filename = format!("{}.{extension}", self.emoji_name());
+        let path = format!("attachments/{channel_id}/{id}/{filename}");
+
+        let is_visual = content_type.starts_with("image/") || content_type.starts_with("video/");
+        let (width, height) = if is_visual {
+            (Some(*self.pick(IMAGE_SIZES)), Some(*self.pick(IMAGE_SIZES)))
+        } else {
+            (None, None)
+        };
+
+        Attachment {
+            content_type: Some(content_type.to_string()),
+            ephemeral: false,
+            filename,
+            description: None,
+            height,
+            id,
+            proxy_url: format!("https://media.discordapp.net/{path}"),
+            size: self.rng.gen_range(1_000..8_000_000),
+            url: format!("https://cdn.discordapp.com/{path}"),
+            width,
+        }
+    }
+}
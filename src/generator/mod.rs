@@ -0,0 +1,218 @@
+use std::{
+    ops::{Range, RangeInclusive},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use twilight_model::{
+    guild::{Guild, Permissions},
+    id::Id,
+    user::User,
+    util::{ImageHash, Timestamp},
+};
+
+use self::presence::UserPresence;
+use crate::config::Config;
+
+mod channel;
+mod emoji;
+mod guild;
+mod member;
+mod message;
+mod names;
+mod presence;
+mod role;
+mod sticker;
+mod user;
+mod voice_state;
+
+/// Milliseconds between the unix epoch and the first second of 2015, which is
+/// what Discord snowflakes are relative to.
+const DISCORD_EPOCH: u64 = 1_420_070_400_000;
+/// Timestamps of generated entities are between the public release of Discord
+/// and the start of 2023. The upper bound is fixed rather than the current
+/// time so that the same seed always generates the same snowflakes.
+const GENERATION_START: u64 = 1_431_475_200_000;
+const GENERATION_END: u64 = 1_672_531_200_000;
+
+/// The global user pool that guild members are drawn from.
+pub struct UserPool {
+    pub users: Vec<User>,
+    /// Presence of each user, or `None` if offline.
+    presences: Vec<Option<UserPresence>>,
+}
+
+/// Everything generated from the `mock_data` section of the config.
+pub struct WorldData {
+    pub pool: UserPool,
+    pub guilds: Vec<Guild>,
+}
+
+/// Deterministic generator for mock data. All randomness is drawn from a
+/// single seeded RNG, so the generation order must not depend on anything but
+/// the seed and the config. Scripts draw from forks of the world's generator.
+pub struct Generator {
+    seed: u64,
+    rng: StdRng,
+    /// Incremented for every snowflake to keep them unique even if two are
+    /// generated with the same timestamp. Shared with forked generators.
+    sequence: Arc<AtomicU64>,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            sequence: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// A generator with its own RNG derived from the seed and the stream, so
+    /// that its draws don't depend on when other generators draw.
+    pub fn fork(&self, stream: u64) -> Self {
+        // Keeps stream 0 apart from the generator of the world
+        let seed = self.seed ^ (stream + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            sequence: self.sequence.clone(),
+        }
+    }
+
+    /// A generator for a task started by the owner of this one, seeded from
+    /// this generator's RNG.
+    pub fn branch(&mut self) -> Self {
+        let stream = self.rng.gen();
+        self.fork(stream)
+    }
+
+    /// Random unix milliseconds between `not_before` and the end of the
+    /// generation period.
+    fn time_after(&mut self, not_before: u64) -> u64 {
+        let start = not_before.clamp(GENERATION_START, GENERATION_END - 1);
+        self.rng.gen_range(start..GENERATION_END)
+    }
+
+    /// Generates a snowflake with a timestamp between `not_before` (unix
+    /// milliseconds) and the end of the generation period.
+    fn snowflake<T>(&mut self, not_before: u64) -> Id<T> {
+        let timestamp = self.time_after(not_before);
+
+        self.snowflake_at(timestamp)
+    }
+
+    /// Generates a snowflake with the given timestamp (unix milliseconds).
+    fn snowflake_at<T>(&mut self, timestamp: u64) -> Id<T> {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        let id = ((timestamp - DISCORD_EPOCH) << 22) | (sequence & 0x3F_FFFF);
+
+        Id::new(id)
+    }
+
+    /// Generates a snowflake for an entity that is created right now.
+    pub fn snowflake_now<T>(&mut self) -> Id<T> {
+        self.snowflake_at(now())
+    }
+
+    fn image_hash(&mut self) -> ImageHash {
+        ImageHash::new(self.rng.gen(), self.rng.gen_bool(0.1))
+    }
+
+    /// Random hex string of the given length, used for session IDs and similar.
+    fn hex(&mut self, len: usize) -> String {
+        (0..len)
+            .map(|_| char::from_digit(self.rng.gen_range(0..16), 16).unwrap())
+            .collect()
+    }
+
+    /// Random subset of the given permissions. Each permission is included
+    /// with a probability of one in four.
+    fn permissions(&mut self, mask: Permissions) -> Permissions {
+        Permissions::from_bits_truncate(self.rng.gen::<u64>() & self.rng.gen::<u64>() & mask.bits())
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        items
+            .choose(&mut self.rng)
+            .expect("Picked from empty slice")
+    }
+
+    /// Picks a random item, or `None` if there are none.
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        items.choose(&mut self.rng)
+    }
+
+    /// Whether something with the given probability happens.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.rng.gen_bool(probability)
+    }
+
+    /// Random duration within the range.
+    pub fn duration(&mut self, range: RangeInclusive<Duration>) -> Duration {
+        self.rng.gen_range(range)
+    }
+
+    fn count(&mut self, range: Range<usize>) -> usize {
+        if range.is_empty() {
+            range.start
+        } else {
+            self.rng.gen_range(range)
+        }
+    }
+
+    pub fn user_pool(&mut self, config: &Config) -> UserPool {
+        let users: Vec<User> = (0..config.mock_data.users).map(|_| self.user()).collect();
+        let presences = users.iter().map(|_| self.user_presence()).collect();
+
+        UserPool { users, presences }
+    }
+
+    pub fn world(&mut self, config: &Config) -> WorldData {
+        let pool = self.user_pool(config);
+        let guilds = (0..config.mock_data.guilds)
+            .map(|_| self.guild(&pool, config))
+            .collect();
+
+        WorldData { pool, guilds }
+    }
+
+    /// Generates a guild that the bot joins right now.
+    pub fn joined_guild(&mut self, pool: &UserPool, config: &Config) -> Guild {
+        let mut guild = self.guild(pool, config);
+        let joined_at = timestamp(now());
+        guild.joined_at = Some(joined_at);
+
+        if let Some(bot_member) = guild
+            .members
+            .iter_mut()
+            .find(|member| member.user.id == config.bot.user_id)
+        {
+            bot_member.joined_at = joined_at;
+        }
+
+        guild
+    }
+}
+
+/// Unix milliseconds at which the entity with the given snowflake was created.
+fn created_at<T>(id: Id<T>) -> u64 {
+    (id.get() >> 22) + DISCORD_EPOCH
+}
+
+/// Current unix milliseconds, for entities that are created at runtime.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+pub fn timestamp(unix_millis: u64) -> Timestamp {
+    Timestamp::from_micros(unix_millis as i64 * 1000).expect("Generated timestamp out of range")
+}
@@ -0,0 +1,189 @@
+use std::ops::Range;
+
+use rand::Rng;
+
+use super::Generator;
+
+const ADJECTIVES: &[&str] = &[
+    "ancient", "brave", "calm", "clever", "cosmic", "crimson", "dizzy", "eager", "fancy", "fuzzy",
+    "gentle", "golden", "happy", "hidden", "icy", "jolly", "lazy", "lucky", "mighty", "misty",
+    "noisy", "odd", "proud", "quiet", "rapid", "rusty", "silent", "sleepy", "spicy", "swift",
+    "tiny", "wild",
+];
+
+const NOUNS: &[&str] = &[
+    "badger", "banana", "castle", "cloud", "comet", "dragon", "falcon", "forest", "gecko",
+    "harbor", "island", "kettle", "lantern", "meadow", "moose", "nebula", "otter", "panda",
+    "pixel", "pumpkin", "raven", "robot", "rocket", "squid", "tiger", "toast", "turtle", "walrus",
+    "wizard", "yeti",
+];
+
+const GUILD_SUFFIXES: &[&str] = &[
+    "Club",
+    "Community",
+    "Den",
+    "Gang",
+    "Guild",
+    "Hangout",
+    "Hideout",
+    "Lounge",
+    "Society",
+];
+
+const CHANNEL_NAMES: &[&str] = &[
+    "general",
+    "off-topic",
+    "memes",
+    "announcements",
+    "rules",
+    "help",
+    "media",
+    "music",
+    "gaming",
+    "bot-commands",
+    "introductions",
+    "art",
+    "feedback",
+    "suggestions",
+    "events",
+    "lounge",
+];
+
+const CATEGORY_NAMES: &[&str] = &[
+    "Information",
+    "General",
+    "Community",
+    "Voice",
+    "Gaming",
+    "Creative",
+    "Staff",
+    "Archive",
+];
+
+const FILLER_WORDS: &[&str] = &[
+    "the", "a", "about", "with", "for", "and", "or", "is", "was", "our", "your", "some", "every",
+    "talk", "share", "discuss", "post", "find", "more", "here",
+];
+
+const TAG_NAMES: &[&str] = &[
+    "question",
+    "solved",
+    "bug",
+    "idea",
+    "discussion",
+    "meta",
+    "showcase",
+    "guide",
+    "help",
+    "off-topic",
+];
+
+const ROLE_NAMES: &[&str] = &[
+    "Admin",
+    "Moderator",
+    "Helper",
+    "Member",
+    "Regular",
+    "VIP",
+    "Muted",
+    "Events",
+    "Artist",
+    "Developer",
+    "Supporter",
+    "Veteran",
+    "Newcomer",
+    "Partner",
+    "Streamer",
+];
+
+const EMOJIS: &[&str] = &["👍", "🎉", "🔥", "❤️", "😂", "🤔", "👀", "✅", "🐢", "🚀"];
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl Generator {
+    pub(super) fn username(&mut self) -> String {
+        let adjective = self.pick(ADJECTIVES);
+        let noun = self.pick(NOUNS);
+
+        if self.rng.gen_bool(0.5) {
+            format!("{adjective}_{noun}{}", self.rng.gen_range(0..100))
+        } else {
+            format!("{}{}", capitalize(adjective), capitalize(noun))
+        }
+    }
+
+    pub(super) fn guild_name(&mut self) -> String {
+        let adjective = self.pick(ADJECTIVES);
+        let noun = self.pick(NOUNS);
+        let suffix = self.pick(GUILD_SUFFIXES);
+
+        format!("{} {} {suffix}", capitalize(adjective), capitalize(noun))
+    }
+
+    pub(super) fn category_name(&mut self) -> String {
+        self.pick(CATEGORY_NAMES).to_uppercase()
+    }
+
+    pub(super) fn role_name(&mut self) -> String {
+        (*self.pick(ROLE_NAMES)).to_string()
+    }
+
+    /// Custom emoji names are alphanumeric with underscores.
+    pub(super) fn emoji_name(&mut self) -> String {
+        format!("{}_{}", self.pick(ADJECTIVES), self.pick(NOUNS))
+    }
+
+    pub(super) fn sticker_name(&mut self) -> String {
+        let adjective = self.pick(ADJECTIVES);
+        let noun = self.pick(NOUNS);
+
+        format!("{} {}", capitalize(adjective), capitalize(noun))
+    }
+
+    pub(super) fn tag_name(&mut self) -> String {
+        (*self.pick(TAG_NAMES)).to_string()
+    }
+
+    pub(super) fn emoji(&mut self) -> String {
+        (*self.pick(EMOJIS)).to_string()
+    }
+
+    /// A sentence of random words with a word count in the given range.
+    pub(super) fn sentence(&mut self, words: Range<usize>) -> String {
+        let count = self.count(words).max(1);
+        let mut sentence = String::new();
+
+        for i in 0..count {
+            let word = match self.rng.gen_range(0..3) {
+                0 => self.pick(ADJECTIVES),
+                1 => self.pick(NOUNS),
+                _ => self.pick(FILLER_WORDS),
+            };
+
+            if i == 0 {
+                sentence.push_str(&capitalize(word));
+            } else {
+                sentence.push(' ');
+                sentence.push_str(word);
+            }
+        }
+
+        sentence.push('.');
+        sentence
+    }
+
+    pub(super) fn channel_name(&mut self) -> String {
+        if self.rng.gen_bool(0.7) {
+            (*self.pick(CHANNEL_NAMES)).to_string()
+        } else {
+            format!("{}-{}", self.pick(ADJECTIVES), self.pick(NOUNS))
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use rand::Rng;
+use twilight_model::{
+    gateway::presence::{
+        Activity, ActivityAssets, ActivityEmoji, ActivityTimestamps, ActivityType, ClientStatus,
+        Presence, Status, UserOrId,
+    },
+    id::{
+        marker::{GuildMarker, UserMarker},
+        Id,
+    },
+};
+
+use super::{Generator, GENERATION_END};
+
+const GAMES: &[&str] = &[
+    "Minecraft",
+    "Terraria",
+    "Rocket League",
+    "Stardew Valley",
+    "Factorio",
+    "Celeste",
+    "Hades",
+    "Portal 2",
+];
+
+const STREAM_URLS: &[&str] = &["https://twitch.tv/discord", "https://youtube.com/discord"];
+
+/// Presence of a user that is not offline. A user has the same presence in
+/// all guilds they are a member of.
+pub struct UserPresence {
+    status: Status,
+    activities: Vec<Activity>,
+    client_status: ClientStatus,
+}
+
+impl UserPresence {
+    pub fn for_guild(&self, user_id: Id<UserMarker>, guild_id: Id<GuildMarker>) -> Presence {
+        Presence {
+            activities: self.activities.clone(),
+            client_status: self.client_status.clone(),
+            guild_id,
+            status: self.status,
+            user: UserOrId::UserId { id: user_id },
+        }
+    }
+}
+
+fn activity(kind: ActivityType, name: String) -> Activity {
+    Activity {
+        application_id: None,
+        assets: None,
+        buttons: Vec::new(),
+        created_at: None,
+        details: None,
+        emoji: None,
+        flags: None,
+        id: None,
+        instance: None,
+        kind,
+        name,
+        party: None,
+        secrets: None,
+        state: None,
+        timestamps: None,
+        url: None,
+    }
+}
+
+impl Generator {
+    /// Generates the presence of a user, or `None` if they are offline.
+    pub(super) fn user_presence(&mut self) -> Option<UserPresence> {
+        let status = match self.rng.gen_range(0..10) {
+            0..=3 => return None,
+            4..=6 => Status::Online,
+            7..=8 => Status::Idle,
+            _ => Status::DoNotDisturb,
+        };
+
+        let mut client_status = ClientStatus {
+            desktop: None,
+            mobile: None,
+            web: None,
+        };
+
+        // Users are connected with at least one client
+        match self.rng.gen_range(0..6) {
+            0..=2 => client_status.desktop = Some(status),
+            3..=4 => client_status.mobile = Some(status),
+            _ => {
+                client_status.desktop = Some(status);
+                client_status.web = Some(Status::Online);
+            }
+        }
+
+        let activities = (0..self.count(0..3)).map(|_| self.activity()).collect();
+
+        Some(UserPresence {
+            status,
+            activities,
+            client_status,
+        })
+    }
+
+    fn activity(&mut self) -> Activity {
+        let started_at = self.time_after(GENERATION_END - 86_400_000);
+
+        let mut activity = match self.rng.gen_range(0..5) {
+            0 => {
+                let mut activity = activity(ActivityType::Custom, String::from("Custom Status"));
+                activity.state = Some(self.sentence(1..6));
+                activity.emoji = self.rng.gen_bool(0.5).then(|| ActivityEmoji {
+                    animated: None,
+                    name: self.emoji(),
+                    id: None,
+                });
+                activity
+            }
+            1 => {
+                let mut activity = activity(ActivityType::Listening, String::from("Spotify"));
+                activity.details = Some(self.sentence(1..4));
+                activity.state = Some(self.username());
+                activity.assets = Some(ActivityAssets {
+                    large_image: Some(format!("spotify:{}", self.hex(40))),
+                    large_text: Some(self.sentence(1..4)),
+                    small_image: None,
+                    small_text: None,
+                });
+                activity
+            }
+            2 => {
+                let mut activity =
+                    activity(ActivityType::Streaming, (*self.pick(GAMES)).to_string());
+                activity.details = Some(self.sentence(2..8));
+                activity.url = Some((*self.pick(STREAM_URLS)).to_string());
+                activity
+            }
+            3 => activity(ActivityType::Competing, (*self.pick(GAMES)).to_string()),
+            _ => {
+                let mut activity = activity(ActivityType::Playing, (*self.pick(GAMES)).to_string());
+                activity.timestamps = Some(ActivityTimestamps {
+                    end: None,
+                    start: Some(started_at),
+                });
+                activity
+            }
+        };
+
+        activity.created_at = Some(started_at);
+
+        activity
+    }
+}
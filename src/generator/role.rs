@@ -0,0 +1,147 @@
+use rand::{seq::SliceRandom, Rng};
+use twilight_model::{
+    guild::{Member, Permissions, Role, RoleTags},
+    id::{
+        marker::{GuildMarker, RoleMarker},
+        Id,
+    },
+};
+
+use super::{created_at, Generator};
+
+/// Permissions of `@everyone` in a newly created guild.
+const DEFAULT_PERMISSIONS: Permissions = Permissions::CREATE_INVITE
+    .union(Permissions::ADD_REACTIONS)
+    .union(Permissions::STREAM)
+    .union(Permissions::VIEW_CHANNEL)
+    .union(Permissions::SEND_MESSAGES)
+    .union(Permissions::EMBED_LINKS)
+    .union(Permissions::ATTACH_FILES)
+    .union(Permissions::READ_MESSAGE_HISTORY)
+    .union(Permissions::USE_EXTERNAL_EMOJIS)
+    .union(Permissions::CONNECT)
+    .union(Permissions::SPEAK)
+    .union(Permissions::USE_VAD)
+    .union(Permissions::CHANGE_NICKNAME)
+    .union(Permissions::USE_SLASH_COMMANDS)
+    .union(Permissions::REQUEST_TO_SPEAK)
+    .union(Permissions::CREATE_PUBLIC_THREADS)
+    .union(Permissions::CREATE_PRIVATE_THREADS)
+    .union(Permissions::USE_EXTERNAL_STICKERS)
+    .union(Permissions::SEND_MESSAGES_IN_THREADS)
+    .union(Permissions::USE_EMBEDDED_ACTIVITIES);
+
+fn base_role(id: Id<RoleMarker>, name: String, permissions: Permissions) -> Role {
+    Role {
+        color: 0,
+        hoist: false,
+        icon: None,
+        id,
+        managed: false,
+        mentionable: false,
+        name,
+        permissions,
+        position: 0,
+        tags: None,
+        unicode_emoji: None,
+    }
+}
+
+impl Generator {
+    /// Generates the `@everyone` role, `count` regular roles, a managed role
+    /// for every bot member and sometimes a booster role. The `@everyone` role
+    /// is first, the order of the others is random.
+    pub(super) fn roles(
+        &mut self,
+        guild_id: Id<GuildMarker>,
+        count: u32,
+        members: &[Member],
+    ) -> Vec<Role> {
+        let mut roles = Vec::new();
+
+        for _ in 0..count {
+            let mut role = self.role(guild_id);
+            role.name = self.role_name();
+            roles.push(role);
+        }
+
+        for member in members.iter().filter(|member| member.user.bot) {
+            let mut role = self.role(guild_id);
+            role.name = member.user.name.clone();
+            role.managed = true;
+            role.tags = Some(RoleTags {
+                available_for_purchase: false,
+                bot_id: Some(member.user.id),
+                guild_connections: false,
+                integration_id: None,
+                premium_subscriber: false,
+                subscription_listing_id: None,
+            });
+            roles.push(role);
+        }
+
+        if self.rng.gen_bool(0.5) {
+            let mut role = self.role(guild_id);
+            role.name = String::from("Server Booster");
+            role.color = 0xF47FFF;
+            role.managed = true;
+            role.permissions = Permissions::empty();
+            role.tags = Some(RoleTags {
+                available_for_purchase: false,
+                bot_id: None,
+                guild_connections: false,
+                integration_id: None,
+                premium_subscriber: true,
+                subscription_listing_id: None,
+            });
+            roles.push(role);
+        }
+
+        roles.shuffle(&mut self.rng);
+
+        for (position, role) in roles.iter_mut().enumerate() {
+            role.position = position as i64 + 1;
+        }
+
+        // The @everyone role shares its ID with the guild
+        let everyone = base_role(
+            guild_id.cast(),
+            String::from("@everyone"),
+            DEFAULT_PERMISSIONS,
+        );
+        roles.insert(0, everyone);
+
+        roles
+    }
+
+    /// A role with random color, icon and permissions, but without name or
+    /// position.
+    fn role(&mut self, guild_id: Id<GuildMarker>) -> Role {
+        let id = self.snowflake(created_at(guild_id));
+
+        // Spread permissions over everything from no permissions at all to
+        // administrator, so that permission calculation has some work to do
+        let permissions = match self.rng.gen_range(0..20) {
+            0 => Permissions::ADMINISTRATOR,
+            1..=3 => Permissions::empty(),
+            _ => self.permissions(Permissions::all()),
+        };
+
+        let mut role = base_role(id, String::new(), permissions);
+        role.color = if self.rng.gen_bool(0.7) {
+            self.rng.gen_range(1..=0xFFFFFF)
+        } else {
+            0
+        };
+        role.hoist = self.rng.gen_bool(0.3);
+        role.mentionable = self.rng.gen_bool(0.4);
+
+        match self.rng.gen_range(0..10) {
+            0 => role.icon = Some(self.image_hash()),
+            1 => role.unicode_emoji = Some(self.emoji()),
+            _ => {}
+        }
+
+        role
+    }
+}
@@ -0,0 +1,39 @@
+use rand::Rng;
+use twilight_model::{
+    channel::message::sticker::{Sticker, StickerFormatType, StickerType},
+    id::{marker::GuildMarker, Id},
+};
+
+use super::{created_at, Generator};
+
+/// Lottie is only available for standard stickers.
+const FORMAT_TYPES: &[StickerFormatType] = &[
+    StickerFormatType::Png,
+    StickerFormatType::Png,
+    StickerFormatType::Apng,
+    StickerFormatType::Gif,
+];
+
+impl Generator {
+    pub(super) fn stickers(&mut self, guild_id: Id<GuildMarker>, count: u32) -> Vec<Sticker> {
+        (0..count)
+            .map(|_| {
+                let id = self.snowflake(created_at(guild_id));
+
+                Sticker {
+                    available: self.rng.gen_bool(0.95),
+                    description: self.rng.gen_bool(0.7).then(|| self.sentence(2..10)),
+                    format_type: *self.pick(FORMAT_TYPES),
+                    guild_id: Some(guild_id),
+                    id,
+                    kind: StickerType::Guild,
+                    name: self.sticker_name(),
+                    pack_id: None,
+                    sort_value: None,
+                    tags: self.emoji(),
+                    user: None, // Omitted in GUILD_CREATE
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,38 @@
+use rand::Rng;
+use twilight_model::user::{User, UserFlags};
+
+use super::{Generator, GENERATION_START};
+
+const PUBLIC_FLAGS: &[UserFlags] = &[
+    UserFlags::empty(),
+    UserFlags::HYPESQUAD_ONLINE_HOUSE_1,
+    UserFlags::HYPESQUAD_ONLINE_HOUSE_2,
+    UserFlags::HYPESQUAD_ONLINE_HOUSE_3,
+    UserFlags::ACTIVE_DEVELOPER,
+];
+
+impl Generator {
+    pub(super) fn user(&mut self) -> User {
+        let id = self.snowflake(GENERATION_START);
+        let avatar = self.rng.gen_bool(0.8).then(|| self.image_hash());
+        let bot = self.rng.gen_bool(0.05);
+
+        User {
+            accent_color: None, // Not sent in the gateway
+            avatar,
+            banner: None, // Not sent in the gateway
+            bot,
+            discriminator: self.rng.gen_range(1..10000),
+            email: None,
+            flags: None,
+            id,
+            locale: None,
+            mfa_enabled: None,
+            name: self.username(),
+            premium_type: None,
+            public_flags: Some(*self.pick(PUBLIC_FLAGS)),
+            system: None,
+            verified: None,
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use rand::{seq::index::sample, Rng};
+use twilight_model::{
+    channel::{Channel, ChannelType},
+    guild::Member,
+    voice::VoiceState,
+};
+
+use super::Generator;
+
+impl Generator {
+    /// Puts up to `count` distinct members into random voice channels.
+    pub(super) fn voice_states(
+        &mut self,
+        members: &[Member],
+        channels: &[Channel],
+        count: usize,
+    ) -> Vec<VoiceState> {
+        let voice_channels: Vec<&Channel> = channels
+            .iter()
+            .filter(|channel| {
+                matches!(
+                    channel.kind,
+                    ChannelType::GuildVoice | ChannelType::GuildStageVoice
+                )
+            })
+            .collect();
+
+        if voice_channels.is_empty() {
+            return Vec::new();
+        }
+
+        sample(&mut self.rng, members.len(), count.min(members.len()))
+            .into_iter()
+            .map(|idx| {
+                let member = &members[idx];
+                let channel = self.pick(&voice_channels);
+                let self_mute = self.rng.gen_bool(0.3);
+
+                VoiceState {
+                    channel_id: Some(channel.id),
+                    deaf: member.deaf,
+                    guild_id: None, // Omitted in GUILD_CREATE
+                    member: None,   // Omitted in GUILD_CREATE
+                    mute: member.mute,
+                    self_deaf: self_mute && self.rng.gen_bool(0.5),
+                    self_mute,
+                    self_stream: self.rng.gen_bool(0.05),
+                    self_video: self.rng.gen_bool(0.05),
+                    session_id: self.hex(32),
+                    suppress: false,
+                    user_id: member.user.id,
+                    request_to_speak_timestamp: None,
+                }
+            })
+            .collect()
+    }
+}
@@ -1,18 +1,23 @@
 use std::{
     borrow::Cow,
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicU64, AtomicUsize, Ordering},
         Arc, OnceLock,
     },
+    time::Duration,
 };
 
 use futures_util::{
     stream::{SplitSink, SplitStream},
     SinkExt, StreamExt,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use simd_json::OwnedValue;
-use tokio::{net::TcpStream, sync::mpsc};
+use tokio::{
+    net::TcpStream,
+    sync::{broadcast, mpsc, watch},
+    time::{self, Instant},
+};
 use tokio_tungstenite::{
     tungstenite::{
         protocol::{frame::coding::CloseCode, CloseFrame},
@@ -21,18 +26,29 @@ use tokio_tungstenite::{
     WebSocketStream,
 };
 use tracing::{debug, enabled, error, info, trace, Level};
-use twilight_model::gateway::{
-    payload::{
-        incoming::{Hello, Ready},
-        outgoing::{identify::IdentifyInfo, resume::ResumeInfo},
+use twilight_model::{
+    channel::Message as ChannelMessage,
+    gateway::{
+        payload::{
+            incoming::{Hello, MemberChunk, Ready},
+            outgoing::{
+                identify::IdentifyInfo,
+                request_guild_members::{RequestGuildMemberId, RequestGuildMembersInfo},
+                resume::ResumeInfo,
+                update_presence::UpdatePresencePayload,
+                update_voice_state::UpdateVoiceStateInfo,
+            },
+        },
+        Intents, OpCode, ShardId,
     },
-    OpCode, ShardId,
+    guild::{Guild, Member, UnavailableGuild},
 };
 
 use crate::{
     config::CONFIG,
-    script,
+    script::Scripts,
     session::{Session, Sessions},
+    world::World,
 };
 
 const HEARTBEAT_INTERVAL: u64 = 41250;
@@ -40,11 +56,15 @@ const PAYLOAD_DECODE_ERROR_MSG: &str = "Error while decoding payload.";
 const DISALLOWED_INTENTS_ERROR_MSG: &str = "Disallowed intent(s).";
 const AUTHENTICATION_FAILED_ERROR_MSG: &str = "Authentication failed.";
 const READY_VERSION: u64 = 6;
+const MEMBER_CHUNK_SIZE: usize = 1000;
+const MAX_MEMBER_REQUEST_LIMIT: usize = 100;
+/// How long clients have to reply to the close frame.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Debug)]
 pub enum Error {
     Websocket(TungsteniteError),
-    Sending(mpsc::error::SendError<Message>),
+    Sending(mpsc::error::SendError<WriterMessage>),
 }
 
 impl From<TungsteniteError> for Error {
@@ -53,8 +73,8 @@ impl From<TungsteniteError> for Error {
     }
 }
 
-impl From<mpsc::error::SendError<Message>> for Error {
-    fn from(value: mpsc::error::SendError<Message>) -> Self {
+impl From<mpsc::error::SendError<WriterMessage>> for Error {
+    fn from(value: mpsc::error::SendError<WriterMessage>) -> Self {
         Self::Sending(value)
     }
 }
@@ -84,6 +104,14 @@ impl GatewayEvent {
         }
     }
 
+    pub fn into_request_guild_members(self) -> Result<RequestGuildMembersInfo, PayloadError> {
+        if let Some(data) = self.d {
+            data.into_request_guild_members()
+        } else {
+            Err(PayloadError::InvalidData)
+        }
+    }
+
     pub fn heartbeat_ack() -> Self {
         Self {
             t: None,
@@ -93,11 +121,21 @@ impl GatewayEvent {
         }
     }
 
+    pub fn reconnect() -> Self {
+        Self {
+            t: None,
+            s: None,
+            op: OpCode::Reconnect,
+            d: None,
+        }
+    }
+
+    /// Asks the client to send a heartbeat immediately.
     pub fn heartbeat() -> Self {
         Self {
             t: None,
             s: None,
-            op: OpCode::HeartbeatAck,
+            op: OpCode::Heartbeat,
             d: None,
         }
     }
@@ -109,17 +147,34 @@ pub enum GatewayEventData {
     Hello(Hello),
     Identify(IdentifyInfo),
     Resume(ResumeInfo),
+    // Voice state updates would also match member requests
+    VoiceStateUpdate(UpdateVoiceStateInfo),
+    RequestGuildMembers(RequestGuildMembersInfo),
+    PresenceUpdate(UpdatePresencePayload),
     InvalidSession(bool),
     Ready(Ready),
+    GuildCreate(Box<Guild>),
+    MemberChunk(MemberChunk),
+    MessageCreate(Box<ChannelMessage>),
     Heartbeat(u64),
+    #[serde(skip_deserializing, serialize_with = "serialize_raw_dispatch")]
     RawDispatch {
-        #[serde(skip)]
         event_type: String,
         data: OwnedValue,
     },
     Resumed,
 }
 
+/// Raw dispatches are sent as their data, the event type is only used for
+/// the `t` field of the payload.
+fn serialize_raw_dispatch<T, S: Serializer>(
+    _event_type: &T,
+    data: &OwnedValue,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    data.serialize(serializer)
+}
+
 pub enum PayloadError {
     InvalidData,
 }
@@ -131,10 +186,16 @@ impl GatewayEventData {
         })
     }
 
-    pub fn ready(session_id: String, shard: Option<ShardId>) -> Self {
+    pub fn ready(session_id: String, shard: Option<ShardId>, guilds: &[Guild]) -> Self {
         Self::Ready(Ready {
             application: (&CONFIG.bot).into(),
-            guilds: Vec::new(), // TODO
+            guilds: guilds
+                .iter()
+                .map(|guild| UnavailableGuild {
+                    id: guild.id,
+                    unavailable: true,
+                })
+                .collect(),
             resume_gateway_url: CONFIG.externally_accessible_url.clone(),
             session_id,
             shard,
@@ -143,6 +204,159 @@ impl GatewayEventData {
         })
     }
 
+    /// GUILD_CREATE for the guild as seen by a session with the given
+    /// intents and large threshold.
+    ///
+    /// Guilds with more members than the threshold are marked as large and
+    /// only contain online members, members in voice channels and the bot.
+    /// The other members have to be requested via op 8.
+    pub fn guild_create(guild: &Guild, intents: Intents, large_threshold: u64) -> Self {
+        let mut guild = guild.clone();
+        let member_count = guild.member_count.unwrap_or(guild.members.len() as u64);
+
+        if member_count > large_threshold {
+            guild.large = true;
+            guild.members.retain(|member| {
+                let user_id = member.user.id;
+
+                user_id == CONFIG.bot.user_id
+                    || guild
+                        .presences
+                        .iter()
+                        .any(|presence| presence.user.id() == user_id)
+                    || guild
+                        .voice_states
+                        .iter()
+                        .any(|voice_state| voice_state.user_id == user_id)
+            });
+        }
+
+        if !intents.contains(Intents::GUILD_PRESENCES) {
+            guild.presences.clear();
+        }
+
+        Self::GuildCreate(Box::new(guild))
+    }
+
+    /// GUILD_MEMBERS_CHUNKs answering a request for members of the guild.
+    pub fn member_chunks(
+        guild: &Guild,
+        request: RequestGuildMembersInfo,
+        intents: Intents,
+    ) -> Vec<Self> {
+        let mut not_found = Vec::new();
+
+        let members: Vec<&Member> = if let Some(user_ids) = request.user_ids {
+            let user_ids = match user_ids {
+                RequestGuildMemberId::One(user_id) => vec![user_id],
+                RequestGuildMemberId::Multiple(user_ids) => user_ids,
+            };
+
+            user_ids
+                .into_iter()
+                .take(MAX_MEMBER_REQUEST_LIMIT)
+                .filter_map(|user_id| {
+                    let member = guild
+                        .members
+                        .iter()
+                        .find(|member| member.user.id == user_id);
+
+                    if member.is_none() {
+                        not_found.push(user_id);
+                    }
+
+                    member
+                })
+                .collect()
+        } else {
+            let query = request.query.unwrap_or_default().to_lowercase();
+
+            // A limit of 0 is only allowed when requesting all members
+            let limit = match request.limit {
+                Some(0) | None if query.is_empty() => usize::MAX,
+                Some(limit) => (limit as usize).min(MAX_MEMBER_REQUEST_LIMIT),
+                None => MAX_MEMBER_REQUEST_LIMIT,
+            };
+
+            // Like Discord, the query matches usernames and nicknames
+            let matches_query = |name: &str| name.to_lowercase().starts_with(&query);
+
+            guild
+                .members
+                .iter()
+                .filter(|member| {
+                    matches_query(&member.user.name)
+                        || matches!(member.nick.as_deref(), Some(nick) if matches_query(nick))
+                })
+                .take(limit)
+                .collect()
+        };
+
+        let with_presences =
+            request.presences == Some(true) && intents.contains(Intents::GUILD_PRESENCES);
+        let chunks: Vec<&[&Member]> = if members.is_empty() {
+            vec![&[]]
+        } else {
+            members.chunks(MEMBER_CHUNK_SIZE).collect()
+        };
+        let chunk_count = chunks.len() as u32;
+
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(chunk_index, members)| {
+                let presences = if with_presences {
+                    guild
+                        .presences
+                        .iter()
+                        .filter(|presence| {
+                            members
+                                .iter()
+                                .any(|member| member.user.id == presence.user.id())
+                        })
+                        .cloned()
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+
+                Self::MemberChunk(MemberChunk {
+                    chunk_count,
+                    chunk_index: chunk_index as u32,
+                    guild_id: guild.id,
+                    members: members.iter().map(|member| (*member).clone()).collect(),
+                    nonce: request.nonce.clone(),
+                    // Only the first chunk lists the IDs that were not found
+                    not_found: if chunk_index == 0 {
+                        not_found.clone()
+                    } else {
+                        Vec::new()
+                    },
+                    presences,
+                })
+            })
+            .collect()
+    }
+
+    /// MESSAGE_CREATE as seen by a session with the given intents. Without
+    /// the message content intent, the content is only included if the bot
+    /// is the author or mentioned.
+    pub fn message_create(mut message: ChannelMessage, intents: Intents) -> Self {
+        let bot_id = CONFIG.bot.user_id;
+
+        if !intents.contains(Intents::MESSAGE_CONTENT)
+            && message.author.id != bot_id
+            && !message.mentions.iter().any(|mention| mention.id == bot_id)
+        {
+            message.content.clear();
+            message.embeds.clear();
+            message.attachments.clear();
+            message.components.clear();
+        }
+
+        Self::MessageCreate(Box::new(message))
+    }
+
     pub fn raw_dispatch(event_type: String, data: OwnedValue) -> Self {
         Self::RawDispatch { event_type, data }
     }
@@ -163,10 +377,21 @@ impl GatewayEventData {
         }
     }
 
+    pub fn into_request_guild_members(self) -> Result<RequestGuildMembersInfo, PayloadError> {
+        if let Self::RequestGuildMembers(request) = self {
+            Ok(request)
+        } else {
+            Err(PayloadError::InvalidData)
+        }
+    }
+
     pub fn dispatch_event_name(&self) -> Option<&str> {
         match self {
             Self::Ready(_) => Some("READY"),
             Self::Resumed => Some("RESUMED"),
+            Self::GuildCreate(_) => Some("GUILD_CREATE"),
+            Self::MemberChunk(_) => Some("GUILD_MEMBERS_CHUNK"),
+            Self::MessageCreate(_) => Some("MESSAGE_CREATE"),
             Self::RawDispatch { event_type, .. } => Some(event_type),
             _ => None,
         }
@@ -200,6 +425,12 @@ impl From<(u64, GatewayEventData)> for GatewayEvent {
                 op: OpCode::Resume,
                 d: Some(event),
             },
+            GatewayEventData::RequestGuildMembers(_) => Self {
+                t: None,
+                s: None,
+                op: OpCode::RequestGuildMembers,
+                d: Some(event),
+            },
             GatewayEventData::InvalidSession(_) => Self {
                 t: None,
                 s: None,
@@ -222,21 +453,100 @@ impl From<(u64, GatewayEventData)> for GatewayEvent {
     }
 }
 
+/// What the client sent, as seen by scripts expecting it or handling it.
+#[derive(Clone, Debug)]
+pub enum ReceivedEvent {
+    Payload {
+        op: OpCode,
+        data: OwnedValue,
+    },
+    /// The client closed the connection with the code.
+    Close(u16),
+}
+
+impl From<&GatewayEvent> for ReceivedEvent {
+    fn from(event: &GatewayEvent) -> Self {
+        let data = event
+            .d
+            .as_ref()
+            .and_then(|data| simd_json::serde::to_owned_value(data).ok())
+            .unwrap_or_default();
+
+        Self::Payload { op: event.op, data }
+    }
+}
+
+/// Messages for the write forwarding task.
+#[derive(Debug)]
+pub enum WriterMessage {
+    Message(Message),
+    /// Drop the connection after writing everything that was queued before.
+    Abort,
+}
+
+/// Writes the messages to the socket. Sends whether the connection was
+/// aborted to the reader once it stops.
 async fn write_forward_task(
     mut sink: SplitSink<WebSocketStream<TcpStream>, Message>,
-    mut rx: mpsc::UnboundedReceiver<Message>,
+    mut rx: mpsc::UnboundedReceiver<WriterMessage>,
+    queued: Arc<AtomicUsize>,
+    shutdown: watch::Sender<bool>,
 ) {
-    while let Some(msg) = rx.recv().await {
-        if sink.send(msg).await.is_err() {
+    // Set once the close frame was sent, the client has to reply until then
+    let mut close_deadline = None;
+    let mut aborted = false;
+
+    loop {
+        let msg = tokio::select! {
+            msg = rx.recv() => msg,
+            _ = time::sleep_until(close_deadline.unwrap_or_else(Instant::now)),
+                if close_deadline.is_some() =>
+            {
+                info!("Client did not complete the close handshake within {CLOSE_TIMEOUT:?}, dropping connection");
+                break;
+            }
+        };
+
+        let Some(msg) = msg else {
             break;
         };
+
+        queued.fetch_sub(1, Ordering::Relaxed);
+
+        match msg {
+            // Nothing can be sent after the close frame
+            WriterMessage::Message(msg) if close_deadline.is_some() => {
+                debug!("Not sending {msg:?} after the close frame");
+            }
+            WriterMessage::Message(msg) => {
+                let is_close = msg.is_close();
+
+                if sink.send(msg).await.is_err() {
+                    break;
+                };
+
+                if is_close {
+                    close_deadline = Some(Instant::now() + CLOSE_TIMEOUT);
+                }
+            }
+            WriterMessage::Abort => {
+                aborted = true;
+                break;
+            }
+        }
     }
+
+    // Stop the reader as well, the socket is dropped together with both halves
+    shutdown.send_replace(aborted);
 }
 
 #[derive(Clone)]
 pub struct WriteHandle {
-    sender: mpsc::UnboundedSender<Message>,
+    sender: mpsc::UnboundedSender<WriterMessage>,
     sequence: Arc<AtomicU64>,
+    /// Amount of messages that were not picked up by the write forwarding
+    /// task yet.
+    queued: Arc<AtomicUsize>,
 }
 
 impl WriteHandle {
@@ -244,7 +554,9 @@ impl WriteHandle {
         match simd_json::to_string(&event) {
             Ok(json) => {
                 debug!("Sending {json} to client");
-                self.sender.send(Message::Text(json))?;
+                self.queued.fetch_add(1, Ordering::Relaxed);
+                self.sender
+                    .send(WriterMessage::Message(Message::Text(json)))?;
             }
             Err(e) => {
                 error!("Failed to serialize {event:?} to JSON due to {e}");
@@ -268,26 +580,62 @@ impl WriteHandle {
         Ok(())
     }
 
+    /// The sequence number of the next dispatch.
+    pub fn next_sequence(&self) -> u64 {
+        self.sequence.load(Ordering::Relaxed)
+    }
+
     pub fn send_raw(&self, msg: Message) -> Result<(), Error> {
-        self.sender.send(msg)?;
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        self.sender.send(WriterMessage::Message(msg))?;
         Ok(())
     }
 
-    fn close(&self, close_code: CloseCode, reason: &'static str) -> Result<(), Error> {
+    /// Amount of messages waiting to be written to the socket.
+    pub fn queue_depth(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    pub fn close(
+        &self,
+        close_code: CloseCode,
+        reason: impl Into<Cow<'static, str>>,
+    ) -> Result<(), Error> {
         self.send_raw(Message::Close(Some(CloseFrame {
             code: close_code,
-            reason: Cow::Borrowed(reason),
+            reason: reason.into(),
         })))?;
 
         Ok(())
     }
+
+    /// Drops the connection without a close handshake after writing what was
+    /// sent before.
+    pub fn abort(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        let _ = self.sender.send(WriterMessage::Abort);
+    }
+
+    /// Waits until the connection is gone.
+    pub async fn closed(&self) {
+        self.sender.closed().await;
+    }
+
+    /// Whether the connection is gone and nothing can be sent anymore.
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
 }
 
 #[derive(Clone)]
 pub struct ConnectionState {
     pub writer: WriteHandle,
-    sessions: Sessions,
+    pub sessions: Sessions,
+    pub world: World,
+    scripts: Scripts,
     session_id: Arc<OnceLock<String>>,
+    /// Where received payloads go once the connection runs a script.
+    received: Arc<OnceLock<broadcast::Sender<ReceivedEvent>>>,
 }
 
 impl ConnectionState {
@@ -297,6 +645,10 @@ impl ConnectionState {
             .and_then(|id| self.sessions.get_session(id))
     }
 
+    pub fn session_id(&self) -> Option<&String> {
+        self.session_id.get()
+    }
+
     pub fn set_session_id(&self, session_id: String) {
         let _ = self.session_id.set(session_id);
     }
@@ -312,8 +664,13 @@ impl ConnectionState {
         Ok(())
     }
 
-    fn set_ready(&self) {
-        tokio::spawn(script::run(self.clone()));
+    /// Forwards payloads received from now on to the script.
+    pub fn set_received(&self, received: broadcast::Sender<ReceivedEvent>) {
+        let _ = self.received.set(received);
+    }
+
+    fn set_ready(&self, shard: Option<ShardId>, resumed: bool) {
+        self.scripts.ready(self.clone(), shard, resumed);
     }
 
     fn process(&self, event: GatewayEvent) -> Result<(), Error> {
@@ -334,14 +691,21 @@ impl ConnectionState {
                         return Ok(());
                     }
 
-                    let session_id = self.sessions.create_session(&data);
+                    let session_id = self.sessions.create_session(&data, self.writer.clone());
                     self.set_session_id(session_id.clone());
+                    let guilds = self.world.guilds_for_shard(data.shard);
                     self.writer
-                        .send_data(GatewayEventData::ready(session_id, data.shard))?;
-
-                    // TODO: Startup GUILD_CREATE payloads
+                        .send_data(GatewayEventData::ready(session_id, data.shard, &guilds))?;
+
+                    for guild in &guilds {
+                        self.writer.send_data(GatewayEventData::guild_create(
+                            guild,
+                            data.intents,
+                            data.large_threshold,
+                        ))?;
+                    }
 
-                    self.set_ready();
+                    self.set_ready(data.shard, false);
 
                     info!("Client has identified");
                 } else {
@@ -358,9 +722,13 @@ impl ConnectionState {
 
                     if self.sessions.exists(&data.session_id) && !CONFIG.scenarios.expired_sessions
                     {
+                        self.sessions
+                            .resume_session(&data.session_id, self.writer.clone());
                         self.set_session_id(data.session_id);
                         self.writer.send_data(GatewayEventData::Resumed)?;
-                        self.set_ready();
+
+                        let shard = self.session().and_then(|session| session.shard_id);
+                        self.set_ready(shard, true);
 
                         info!("Client has resumed");
                     } else {
@@ -370,6 +738,41 @@ impl ConnectionState {
                     self.invalidate_session(false)?;
                 }
             }
+            OpCode::RequestGuildMembers => {
+                let Some(session) = self.session() else {
+                    debug!("Ignoring member request before identifying");
+                    return Ok(());
+                };
+
+                if let Ok(request) = event.into_request_guild_members() {
+                    let guild = self
+                        .world
+                        .guild_for_shard(request.guild_id, session.shard_id);
+
+                    let Some(guild) = guild else {
+                        debug!(
+                            "Ignoring member request for unknown guild {}",
+                            request.guild_id
+                        );
+                        return Ok(());
+                    };
+
+                    let requests_all = request.user_ids.is_none()
+                        && request.query.as_deref().unwrap_or_default().is_empty();
+
+                    if requests_all && !session.intents.contains(Intents::GUILD_MEMBERS) {
+                        debug!("Ignoring request for all members without GUILD_MEMBERS intent");
+                        return Ok(());
+                    }
+
+                    for chunk in GatewayEventData::member_chunks(&guild, request, session.intents) {
+                        self.writer.send_data(chunk)?;
+                    }
+                } else {
+                    self.writer
+                        .close(CloseCode::Library(4002), PAYLOAD_DECODE_ERROR_MSG)?;
+                }
+            }
             OpCode::Heartbeat => {
                 if !CONFIG.scenarios.unanswered_heartbeats {
                     // Note: Discord does not validate the heartbeat sequence sent in the data part
@@ -387,27 +790,43 @@ impl ConnectionState {
 pub struct Connection {
     stream: SplitStream<WebSocketStream<TcpStream>>,
     state: ConnectionState,
+    shutdown: watch::Receiver<bool>,
 }
 
 impl Connection {
-    pub fn new(stream: WebSocketStream<TcpStream>, sessions: Sessions) -> Self {
+    pub fn new(
+        stream: WebSocketStream<TcpStream>,
+        sessions: Sessions,
+        world: World,
+        scripts: Scripts,
+    ) -> Self {
         let (sink, stream) = stream.split();
         let (tx, rx) = mpsc::unbounded_channel();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
-        tokio::spawn(write_forward_task(sink, rx));
+        let queued = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(write_forward_task(sink, rx, queued.clone(), shutdown_tx));
 
         let write_handle = WriteHandle {
             sender: tx,
             sequence: Arc::new(AtomicU64::new(0)),
+            queued,
         };
 
         let state = ConnectionState {
             writer: write_handle,
             sessions,
+            world,
+            scripts,
             session_id: Arc::new(OnceLock::new()),
+            received: Arc::new(OnceLock::new()),
         };
 
-        Self { stream, state }
+        Self {
+            stream,
+            state,
+            shutdown: shutdown_rx,
+        }
     }
 
     pub fn send(&self, event: GatewayEvent) -> Result<(), Error> {
@@ -427,9 +846,40 @@ impl Connection {
     }
 
     pub async fn handle(&mut self) -> Result<(), Error> {
+        let result = self.read().await;
+
+        // Stop the write forwarding task if the client closed the connection
+        // or errored, so that the socket is dropped
+        self.state.writer.abort();
+
+        result
+    }
+
+    async fn read(&mut self) -> Result<(), Error> {
         self.send_data(GatewayEventData::hello())?;
 
-        while let Some(Ok(msg)) = self.stream.next().await {
+        loop {
+            let msg = tokio::select! {
+                msg = self.stream.next() => msg,
+                _ = self.shutdown.changed() => {
+                    if *self.shutdown.borrow() {
+                        info!("Dropping connection without close handshake");
+                    }
+
+                    break;
+                }
+            };
+
+            let Some(Ok(msg)) = msg else {
+                break;
+            };
+
+            if let Message::Close(Some(frame)) = &msg {
+                if let Some(sender) = self.state.received.get() {
+                    let _ = sender.send(ReceivedEvent::Close(frame.code.into()));
+                }
+            }
+
             if msg.is_text() || msg.is_binary() {
                 let mut data = msg.into_data();
 
@@ -440,7 +890,25 @@ impl Connection {
                 match simd_json::from_slice::<GatewayEvent>(&mut data) {
                     Ok(event) => {
                         debug!("Got {event:?}");
+
+                        // Only convert the payload if a script might expect it
+                        let received = self
+                            .state
+                            .received
+                            .get()
+                            .map(|sender| sender.receiver_count() > 0)
+                            .unwrap_or(true)
+                            .then(|| ReceivedEvent::from(&event));
+
                         self.state.process(event)?;
+
+                        // Identify and resume link the script first, so that
+                        // it sees them as well
+                        if let (Some(sender), Some(received)) =
+                            (self.state.received.get(), received)
+                        {
+                            let _ = sender.send(received);
+                        }
                     }
                     Err(e) => {
                         error!("Failed to deserialize client event with {e}");
@@ -1,30 +1,64 @@
 #![feature(once_cell, option_result_contains)]
 
 use std::{
-    io,
+    env, io,
     net::{IpAddr, Ipv4Addr, SocketAddr},
     str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
 };
 
 use config::CONFIG;
-use libc::{c_int, sighandler_t, signal, SIGINT, SIGTERM};
-use tokio::net::TcpListener;
+use libc::{c_int, sighandler_t, signal, SIGHUP, SIGINT, SIGTERM};
+use tokio::{net::TcpListener, time};
 use tokio_tungstenite::accept_async;
 use tracing::{error, info};
 use tracing_subscriber::{filter::LevelFilter, layer::SubscriberExt, util::SubscriberInitExt};
 
-use crate::{handler::Connection, session::Sessions};
+use crate::{handler::Connection, script::Scripts, session::Sessions, world::World};
 
+mod check;
 mod config;
+mod generator;
 mod handler;
 mod script;
 mod session;
+mod template;
+mod world;
+
+/// How often the script files are checked for changes.
+const SCRIPT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Set by SIGHUP to reload the scripts.
+static RELOAD_SCRIPTS: AtomicBool = AtomicBool::new(false);
+
+/// Reloads the scripts when a script file changed or on SIGHUP. Changes to
+/// included files and payload files need a SIGHUP.
+async fn watch_scripts() {
+    let mut interval = time::interval(SCRIPT_POLL_INTERVAL);
+    let mut modified = config::scripts_modified();
+
+    loop {
+        interval.tick().await;
+
+        let now_modified = config::scripts_modified();
+
+        if RELOAD_SCRIPTS.swap(false, Ordering::Relaxed) || now_modified != modified {
+            modified = now_modified;
+            config::reload_scripts();
+        }
+    }
+}
 
 async fn run() -> Result<(), io::Error> {
     let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), CONFIG.port);
     let listener = TcpListener::bind(addr).await?;
 
     let sessions = Sessions::new();
+    let world = World::from_config(&CONFIG);
+    let scripts = Scripts::new();
+
+    tokio::spawn(watch_scripts());
 
     info!("Listening on {addr}");
 
@@ -32,10 +66,13 @@ async fn run() -> Result<(), io::Error> {
         info!("Connection from {remote_addr}");
 
         let sessions_clone = sessions.clone();
+        let world_clone = world.clone();
+        let scripts_clone = scripts.clone();
 
         tokio::spawn(async move {
             if let Ok(ws_stream) = accept_async(stream).await {
-                let mut connection = Connection::new(ws_stream, sessions_clone);
+                let mut connection =
+                    Connection::new(ws_stream, sessions_clone, world_clone, scripts_clone);
                 if let Err(e) = connection.handle().await {
                     error!("Websocket handler errored: {e:?}");
                 };
@@ -52,12 +89,22 @@ pub extern "C" fn handler(_: c_int) {
     std::process::exit(0);
 }
 
+pub extern "C" fn reload_handler(_: c_int) {
+    RELOAD_SCRIPTS.store(true, Ordering::Relaxed);
+}
+
 unsafe fn set_os_handlers() {
     signal(SIGINT, handler as extern "C" fn(_) as sighandler_t);
     signal(SIGTERM, handler as extern "C" fn(_) as sighandler_t);
+    signal(SIGHUP, reload_handler as extern "C" fn(_) as sighandler_t);
 }
 
 fn main() {
+    // Validates the config and scripts without binding the port
+    if env::args().skip(1).any(|arg| arg == "--check") {
+        std::process::exit(if check::run() { 0 } else { 1 });
+    }
+
     unsafe { set_os_handlers() };
 
     let level_filter = LevelFilter::from_str(&CONFIG.log_level).unwrap_or(LevelFilter::INFO);
@@ -67,6 +114,8 @@ fn main() {
         .with(level_filter)
         .init();
 
+    config::load_scripts_now();
+
     if let Err(e) = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
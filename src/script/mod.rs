@@ -0,0 +1,702 @@
+use std::{
+    future::Future,
+    num::NonZeroU64,
+    ops::RangeInclusive,
+    pin::{pin, Pin},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_util::future::join_all;
+use simd_json::{prelude::*, OwnedValue};
+use tokio::{
+    sync::{
+        broadcast::{self, error::RecvError},
+        watch,
+    },
+    task::yield_now,
+    time::{self, sleep, Instant},
+};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tracing::{debug, error, info, warn};
+use twilight_model::gateway::{Intents, OpCode, ShardId};
+
+pub use self::parse::{check, parse, ScriptError};
+use crate::{
+    config::{self, CONFIG},
+    generator::Generator,
+    handler::{ConnectionState, GatewayEvent, GatewayEventData, ReceivedEvent},
+    session::Session,
+    template::Renderer,
+};
+
+mod parse;
+
+#[derive(Debug)]
+pub enum Action {
+    Sleep(Duration),
+    /// Sleeps for a random duration within the range.
+    SleepRand(RangeInclusive<Duration>),
+    InvalidateSession(bool),
+    Dispatch {
+        event_type: String,
+        data: OwnedValue,
+    },
+    Heartbeat,
+    /// Asks the client to reconnect and resume. The connection is dropped if
+    /// the client did not close it after the timeout.
+    Reconnect(Option<Duration>),
+    RandomMessageCreate,
+    RandomGuildCreate,
+    /// Dispatches events at a fixed rate for the duration. MESSAGE_CREATEs
+    /// are generated, other events need a payload.
+    Burst {
+        event_type: String,
+        rate: u32,
+        duration: Duration,
+        data: Option<OwnedValue>,
+    },
+    GracefulClose {
+        code: u16,
+        reason: String,
+    },
+    AbruptClose,
+    /// Runs the actions the given amount of times.
+    Repeat(u64, Vec<Action>),
+    /// Runs the actions until the connection is closed.
+    Loop(Vec<Action>),
+    /// Runs the bodies at the same time on the same connection, until all of
+    /// them finished.
+    Parallel(Vec<Vec<Action>>),
+    /// Runs the actions with the given probability.
+    Chance(f64, Vec<Action>),
+    /// Runs the actions if the condition holds for the session.
+    If(Condition, Vec<Action>),
+    /// Waits until the client identified or resumed on a new connection.
+    WaitReady,
+    /// Fails the script if the client does not send the opcode in time,
+    /// optionally with a non-null field in its data.
+    Expect {
+        op: OpCode,
+        within: Duration,
+        field: Option<String>,
+    },
+    /// Runs the actions alongside the script whenever the trigger happens.
+    /// Only allowed at the top level of a script.
+    On(Trigger, Vec<Action>),
+    // add more as needed
+}
+
+#[derive(Debug)]
+pub enum Condition {
+    /// The session's shard is the given one.
+    Shard(u64),
+    /// The session identified with all of the intents.
+    IntentsHas(Intents),
+    /// The session was resumed at least once.
+    Resumed,
+}
+
+#[derive(Debug)]
+pub enum Trigger {
+    /// The client sent the opcode, or sent it for the Nth time in the
+    /// session.
+    Op(OpCode, Option<NonZeroU64>),
+    /// The client closed the connection with the code.
+    Close(u16),
+}
+
+impl Trigger {
+    /// Whether the event triggers the handler. The count is how often the
+    /// handler's opcode was sent before, and is updated.
+    fn matches(&self, event: &ReceivedEvent, count: &mut u64) -> bool {
+        match (self, event) {
+            (Self::Op(op, nth), ReceivedEvent::Payload { op: received, .. }) if op == received => {
+                *count += 1;
+                nth.map(|nth| nth.get() == *count).unwrap_or(true)
+            }
+            (Self::Close(code), ReceivedEvent::Close(received)) => code == received,
+            _ => false,
+        }
+    }
+}
+
+impl Condition {
+    /// Whether the condition holds. Sessions without a shard are shard 0.
+    fn holds(&self, session: &Session) -> bool {
+        match self {
+            Self::Shard(number) => session.shard_id.map_or(0, ShardId::number) == *number,
+            Self::IntentsHas(intents) => session.intents.contains(*intents),
+            Self::Resumed => session.resumed,
+        }
+    }
+}
+
+/// The running scripts. Every session has one script, which continues on
+/// the new connection when the client resumes. A script whose connection is
+/// gone is also continued by the next client that identifies with the same
+/// shard, so that a script can span invalidated sessions.
+#[derive(Clone)]
+pub struct Scripts(Arc<Mutex<Vec<Script>>>);
+
+struct Script {
+    shard: Option<ShardId>,
+    /// The connection the script currently runs on.
+    connection: watch::Sender<ConnectionState>,
+    /// Payloads received on the script's connections.
+    received: broadcast::Sender<ReceivedEvent>,
+}
+
+/// How many received payloads are buffered for expectations.
+const RECEIVED_CAPACITY: usize = 64;
+
+/// How long loops whose iterations run no actions pause between iterations.
+const IDLE_LOOP_PAUSE: Duration = Duration::from_millis(100);
+
+impl Scripts {
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(Vec::new())))
+    }
+
+    /// Continues the script of the connection's session on the connection,
+    /// or starts a new script for newly identified sessions.
+    pub fn ready(&self, state: ConnectionState, shard: Option<ShardId>, resumed: bool) {
+        let mut scripts = self.0.lock().expect("Scripts mutex poisoned");
+
+        // Scripts that finished dropped their receiver
+        scripts.retain(|script| script.connection.receiver_count() > 0);
+
+        let script = scripts
+            .iter()
+            .find(|script| script.connection.borrow().session_id() == state.session_id())
+            .or_else(|| {
+                scripts.iter().find(|script| {
+                    script.shard == shard && script.connection.borrow().writer.is_closed()
+                })
+            });
+
+        if let Some(script) = script {
+            state.set_received(script.received.clone());
+            script.connection.send_replace(state);
+        } else if !resumed {
+            let script = config::script_for(shard);
+            let (received, _) = broadcast::channel(RECEIVED_CAPACITY);
+            state.set_received(received.clone());
+
+            // Subscribe before the identify is forwarded, so that handlers
+            // see it
+            let events = script
+                .iter()
+                .any(|action| matches!(action, Action::On(..)))
+                .then(|| received.subscribe());
+
+            let generator = state.world.script_generator(shard);
+            let (connection, receiver) = watch::channel(state);
+            scripts.push(Script {
+                shard,
+                connection,
+                received: received.clone(),
+            });
+            let runner = Runner {
+                connection: receiver,
+                received,
+                generator,
+                failures: Vec::new(),
+                actions_run: 0,
+            };
+            tokio::spawn(run(runner, script, events));
+        }
+    }
+}
+
+/// A script running for a session.
+struct Runner {
+    connection: watch::Receiver<ConnectionState>,
+    received: broadcast::Sender<ReceivedEvent>,
+    /// Draws of the script, so that the seed reproduces them.
+    generator: Generator,
+    /// Expectations the client did not meet.
+    failures: Vec<String>,
+    /// How many actions outside of blocks ran, to notice idle loops.
+    actions_run: u64,
+}
+
+impl Runner {
+    /// A runner for a parallel branch on the same connection.
+    fn branch(&mut self) -> Self {
+        Self {
+            connection: self.connection.clone(),
+            received: self.received.clone(),
+            generator: self.generator.branch(),
+            failures: Vec::new(),
+            actions_run: 0,
+        }
+    }
+}
+
+/// Runs the script, and its handlers if it has any.
+async fn run(
+    mut runner: Runner,
+    script: Arc<[Action]>,
+    events: Option<broadcast::Receiver<ReceivedEvent>>,
+) {
+    let completed = match events {
+        Some(events) => {
+            let handler_runner = runner.branch();
+            let (completed, failures) = handle(
+                handler_runner,
+                events,
+                script.clone(),
+                run_actions(&mut runner, &script),
+            )
+            .await;
+            runner.failures.extend(failures);
+
+            completed
+        }
+        None => run_actions(&mut runner, &script).await,
+    };
+
+    let session_id = runner
+        .connection
+        .borrow()
+        .session_id()
+        .cloned()
+        .unwrap_or_default();
+
+    // A script that stopped early did not check everything it should have
+    match (completed, runner.failures.is_empty()) {
+        (true, true) => info!("Script for session {session_id} passed"),
+        (false, true) => {
+            error!("Script for session {session_id} is incomplete because the connection closed")
+        }
+        (completed, false) => error!(
+            "Script for session {session_id} {}failed {} expectations: {}",
+            if completed { "" } else { "is incomplete and " },
+            runner.failures.len(),
+            runner.failures.join(", ")
+        ),
+    }
+}
+
+/// Runs the handlers of the script whenever their trigger happens, while the
+/// actions of the script run. Handlers stay active until the actions
+/// finished and the connection is closed. Returns whether the actions ran to
+/// the end, and the expectations the handlers failed.
+async fn handle(
+    mut runner: Runner,
+    mut events: broadcast::Receiver<ReceivedEvent>,
+    script: Arc<[Action]>,
+    actions: impl Future<Output = bool>,
+) -> (bool, Vec<String>) {
+    // Handlers by their index in the script, which their tasks share
+    let handlers: Vec<(usize, &Trigger)> = script
+        .iter()
+        .enumerate()
+        .filter_map(|(index, action)| match action {
+            Action::On(trigger, _) => Some((index, trigger)),
+            _ => None,
+        })
+        .collect();
+
+    let mut actions = pin!(actions);
+    let mut finished = None;
+    let mut counts = vec![0; handlers.len()];
+    let mut running = Vec::new();
+
+    loop {
+        let writer = runner.connection.borrow().writer.clone();
+
+        tokio::select! {
+            // Payloads received before the connection closed still trigger
+            biased;
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(count)) => {
+                        warn!("Handlers missed {count} payloads");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                for (&(index, trigger), count) in handlers.iter().zip(&mut counts) {
+                    if trigger.matches(&event, count) {
+                        info!("Running handler for {trigger:?}");
+
+                        // Like the runner's actions, a handler only waits
+                        // for clients that become ready after it started
+                        let mut branch = runner.branch();
+                        branch.connection.borrow_and_update();
+                        let script = script.clone();
+                        running.push(tokio::spawn(async move {
+                            if let Action::On(_, actions) = &script[index] {
+                                run_actions(&mut branch, actions).await;
+                            }
+
+                            branch.failures
+                        }));
+                    }
+                }
+            }
+            completed = &mut actions, if finished.is_none() => finished = Some(completed),
+            _ = writer.closed(), if finished.is_some() => break,
+        }
+    }
+
+    let failures = join_all(running)
+        .await
+        .into_iter()
+        .flatten()
+        .flatten()
+        .collect();
+
+    (finished.unwrap_or(false), failures)
+}
+
+/// Runs the actions in order. Returns `false` if the script stopped because
+/// the connection is closed.
+fn run_actions<'a>(
+    runner: &'a mut Runner,
+    actions: &'a [Action],
+) -> Pin<Box<dyn Future<Output = bool> + Send + 'a>> {
+    Box::pin(async move {
+        for action in actions {
+            // Actions mark the connection they run on as seen, so that
+            // `wait_ready` only returns for a client that became ready later
+            let state = if matches!(action, Action::WaitReady) {
+                runner.connection.borrow().clone()
+            } else {
+                runner.connection.borrow_and_update().clone()
+            };
+
+            // Waits continue on the next connection, for example after a
+            // `reconnect`
+            let waits = matches!(action, Action::WaitReady | Action::Expect { .. });
+            if state.writer.is_closed() && !waits {
+                info!("Stopping script because the connection is closed");
+                return false;
+            }
+
+            if !matches!(
+                action,
+                Action::Repeat(..)
+                    | Action::Loop(_)
+                    | Action::Parallel(_)
+                    | Action::Chance(..)
+                    | Action::If(..)
+                    | Action::On(..)
+            ) {
+                runner.actions_run += 1;
+            }
+
+            match action {
+                Action::Repeat(count, actions) => {
+                    for _ in 0..*count {
+                        if !run_actions(runner, actions).await {
+                            return false;
+                        }
+                    }
+                }
+                Action::Loop(actions) => {
+                    let mut warned = false;
+
+                    loop {
+                        let actions_run = runner.actions_run;
+
+                        if !run_actions(runner, actions).await {
+                            return false;
+                        }
+
+                        // Blocks that are skipped every time would otherwise
+                        // spin until the connection closes
+                        if runner.actions_run == actions_run {
+                            if !warned {
+                                warn!("Loop iteration ran no actions, pausing between iterations");
+                                warned = true;
+                            }
+
+                            sleep(IDLE_LOOP_PAUSE).await;
+                        } else {
+                            // Don't starve other tasks if the loop never waits
+                            yield_now().await;
+                        }
+                    }
+                }
+                Action::Parallel(bodies) => {
+                    let mut branches: Vec<Runner> =
+                        bodies.iter().map(|_| runner.branch()).collect();
+
+                    let finished = join_all(
+                        branches
+                            .iter_mut()
+                            .zip(bodies)
+                            .map(|(branch, actions)| run_actions(branch, actions)),
+                    )
+                    .await;
+
+                    for branch in branches {
+                        runner.failures.extend(branch.failures);
+                        runner.actions_run += branch.actions_run;
+                    }
+
+                    if finished.contains(&false) {
+                        return false;
+                    }
+                }
+                Action::Chance(probability, actions) => {
+                    if runner.generator.chance(*probability) && !run_actions(runner, actions).await
+                    {
+                        return false;
+                    }
+                }
+                Action::If(condition, actions) => {
+                    let holds =
+                        matches!(state.session(), Some(session) if condition.holds(&session));
+
+                    debug!("Condition {condition:?} is {holds}");
+
+                    if holds && !run_actions(runner, actions).await {
+                        return false;
+                    }
+                }
+                Action::WaitReady => {
+                    info!("Waiting for the client to identify or resume");
+
+                    // Returns at once if the client became ready since the
+                    // previous action started
+                    if runner.connection.changed().await.is_err() {
+                        return false;
+                    }
+                }
+                Action::Expect { op, within, field } => {
+                    info!("Running {action:?}");
+
+                    if !expect(&runner.received, *op, *within, field.as_deref()).await {
+                        let failure = match field {
+                            Some(field) => format!("{op:?} with {field} within {within:?}"),
+                            None => format!("{op:?} within {within:?}"),
+                        };
+
+                        warn!("Client did not send {failure}");
+                        runner.failures.push(failure);
+                    }
+                }
+                // Handlers are run by handle
+                Action::On(..) => {}
+                _ => run_action(&state, &mut runner.generator, action).await,
+            }
+        }
+
+        true
+    })
+}
+
+/// Waits for the client to send the opcode, returning `false` on timeout.
+async fn expect(
+    received: &broadcast::Sender<ReceivedEvent>,
+    op: OpCode,
+    within: Duration,
+    field: Option<&str>,
+) -> bool {
+    let mut received = received.subscribe();
+
+    let matches = |event: &ReceivedEvent| {
+        let ReceivedEvent::Payload { op: received, data } = event else {
+            return false;
+        };
+
+        *received == op
+            && match field {
+                Some(field) => matches!(data.get(field), Some(value) if !value.is_null()),
+                None => true,
+            }
+    };
+
+    time::timeout(within, async {
+        loop {
+            match received.recv().await {
+                Ok(event) if matches(&event) => break,
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                // The runner holds a sender, so this never happens
+                Err(RecvError::Closed) => std::future::pending().await,
+            }
+        }
+    })
+    .await
+    .is_ok()
+}
+
+/// Dispatches events at the rate until the duration elapsed, then reports
+/// the rate that was achieved and how far the writer fell behind.
+async fn burst(
+    state: &ConnectionState,
+    generator: &mut Generator,
+    event_type: &str,
+    rate: u32,
+    duration: Duration,
+    data: Option<&OwnedValue>,
+) {
+    // Generated messages need a session that receives them
+    let session = match data {
+        Some(_) => None,
+        None => match message_session(state) {
+            Ok(session) => Some(session),
+            Err(reason) => {
+                warn!("Not running burst of {event_type} because {reason}");
+                return;
+            }
+        },
+    };
+
+    let start = Instant::now();
+    let mut interval = time::interval(Duration::from_secs(1) / rate);
+    let mut sent: u64 = 0;
+    let mut max_queue_depth = 0;
+
+    while start.elapsed() < duration {
+        interval.tick().await;
+
+        let event = match (data, &session) {
+            (Some(data), _) => {
+                let data = Renderer::new(state, generator).render(data);
+                state.world.apply_raw(event_type, &data);
+                GatewayEventData::raw_dispatch(event_type.to_string(), data)
+            }
+            (None, Some(session)) => {
+                let Some(message) = state.world.random_message(generator, session.shard_id) else {
+                    warn!("Stopping burst because there is no text channel");
+                    return;
+                };
+
+                GatewayEventData::message_create(message, session.intents)
+            }
+            (None, None) => unreachable!("Checked before the burst"),
+        };
+
+        if state.writer.send_data(event).is_err() {
+            info!("Stopping burst because the connection is closed");
+            break;
+        }
+
+        sent += 1;
+        max_queue_depth = max_queue_depth.max(state.writer.queue_depth());
+    }
+
+    let elapsed = start.elapsed();
+    info!(
+        "Burst of {event_type} sent {sent} events in {elapsed:?} ({:.1}/s of {rate}/s), writer queue peaked at {max_queue_depth} and is at {}",
+        sent as f64 / elapsed.as_secs_f64(),
+        state.writer.queue_depth()
+    );
+}
+
+/// The session of the connection if it receives generated messages, or why
+/// it does not.
+fn message_session(state: &ConnectionState) -> Result<Session, &'static str> {
+    let session = state.session().ok_or("the client has not identified")?;
+
+    if !session.intents.contains(Intents::GUILD_MESSAGES) {
+        return Err("the session lacks the GUILD_MESSAGES intent");
+    }
+
+    Ok(session)
+}
+
+async fn run_action(state: &ConnectionState, generator: &mut Generator, action: &Action) {
+    info!("Running {action:?}");
+
+    match action {
+        Action::Sleep(duration) => sleep(*duration).await,
+        Action::SleepRand(range) => sleep(generator.duration(range.clone())).await,
+        Action::InvalidateSession(resumable) => {
+            let _ = state.invalidate_session(*resumable);
+        }
+        Action::Dispatch { event_type, data } => {
+            let data = Renderer::new(state, generator).render(data);
+            state.world.apply_raw(event_type, &data);
+            let event = GatewayEventData::raw_dispatch(event_type.clone(), data);
+            let _ = state.writer.send_data(event);
+        }
+        Action::Heartbeat => {
+            let _ = state.writer.send(GatewayEvent::heartbeat());
+        }
+        Action::Reconnect(timeout) => {
+            let _ = state.writer.send(GatewayEvent::reconnect());
+
+            // The session stays resumable, the client is expected to close
+            // the connection and resume on a new one
+            if let Some(timeout) = timeout {
+                if time::timeout(*timeout, state.writer.closed())
+                    .await
+                    .is_err()
+                {
+                    info!("Client did not reconnect within {timeout:?}, dropping connection");
+                    state.writer.abort();
+                }
+            }
+        }
+        Action::RandomMessageCreate => {
+            let session = match message_session(state) {
+                Ok(session) => session,
+                Err(reason) => {
+                    debug!("Not creating a message because {reason}");
+                    return;
+                }
+            };
+
+            match state.world.random_message(generator, session.shard_id) {
+                Some(message) => {
+                    let event = GatewayEventData::message_create(message, session.intents);
+                    let _ = state.writer.send_data(event);
+                }
+                None => warn!("Skipping action {action:?} because there is no text channel"),
+            }
+        }
+        Action::RandomGuildCreate => {
+            let guild = state.world.random_guild(generator, &CONFIG);
+
+            info!("Bot joined guild {}", guild.id);
+
+            // The guild might be handled by another shard than this one
+            for session in state.sessions.sessions_for_guild(guild.id) {
+                let event = GatewayEventData::guild_create(
+                    &guild,
+                    session.intents,
+                    session.large_threshold,
+                );
+                let _ = session.writer.send_data(event);
+            }
+        }
+        Action::Burst {
+            event_type,
+            rate,
+            duration,
+            data,
+        } => {
+            burst(
+                state,
+                generator,
+                event_type,
+                *rate,
+                *duration,
+                data.as_ref(),
+            )
+            .await
+        }
+        Action::GracefulClose { code, reason } => {
+            let _ = state.writer.close(CloseCode::from(*code), reason.clone());
+        }
+        Action::AbruptClose => state.writer.abort(),
+        Action::Repeat(..)
+        | Action::Loop(_)
+        | Action::Parallel(_)
+        | Action::Chance(..)
+        | Action::If(..)
+        | Action::WaitReady
+        | Action::Expect { .. }
+        | Action::On(..) => {
+            unreachable!("Blocks and waits are run by run_actions")
+        }
+    }
+}
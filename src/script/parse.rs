@@ -0,0 +1,1197 @@
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    fmt::{self, Display},
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::de::DeserializeSeed;
+use simd_json::OwnedValue;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use twilight_model::gateway::{event::DispatchEventWithTypeDeserializer, Intents, OpCode};
+
+use super::{Action, Condition, Trigger};
+use crate::template;
+
+/// Names of all actions, for suggestions.
+const ACTIONS: &[&str] = &[
+    "sleep_ms",
+    "sleep_s",
+    "sleep_rand",
+    "invalidate_session",
+    "dispatch",
+    "heartbeat",
+    "reconnect",
+    "random_message_create",
+    "random_guild_create",
+    "burst",
+    "graceful_close",
+    "abrupt_close",
+    "wait_ready",
+    "expect",
+    "chance",
+    "dispatch_file",
+    "include",
+    "unchecked",
+];
+
+/// Highest rate of a burst, in events per second.
+const MAX_BURST_RATE: u32 = 1_000_000_000;
+
+/// Longest reason of a close frame, in bytes, so that the frame fits in a
+/// control frame.
+const MAX_CLOSE_REASON_LEN: usize = 123;
+
+/// Names of all blocks, for suggestions.
+const BLOCKS: &[&str] = &["repeat", "loop", "if", "chance", "parallel", "spawn", "on"];
+
+/// Names of the dispatch events that twilight-model knows.
+const EVENT_TYPES: &[&str] = &[
+    "AUTO_MODERATION_ACTION_EXECUTION",
+    "AUTO_MODERATION_RULE_CREATE",
+    "AUTO_MODERATION_RULE_DELETE",
+    "AUTO_MODERATION_RULE_UPDATE",
+    "CHANNEL_CREATE",
+    "CHANNEL_DELETE",
+    "CHANNEL_PINS_UPDATE",
+    "CHANNEL_UPDATE",
+    "APPLICATION_COMMAND_PERMISSIONS_UPDATE",
+    "GIFT_CODE_UPDATE",
+    "GUILD_AUDIT_LOG_ENTRY_CREATE",
+    "GUILD_BAN_ADD",
+    "GUILD_BAN_REMOVE",
+    "GUILD_CREATE",
+    "GUILD_DELETE",
+    "GUILD_EMOJIS_UPDATE",
+    "GUILD_INTEGRATIONS_UPDATE",
+    "GUILD_SCHEDULED_EVENT_CREATE",
+    "GUILD_SCHEDULED_EVENT_DELETE",
+    "GUILD_SCHEDULED_EVENT_UPDATE",
+    "GUILD_SCHEDULED_EVENT_USER_ADD",
+    "GUILD_SCHEDULED_EVENT_USER_REMOVE",
+    "GUILD_MEMBERS_CHUNK",
+    "GUILD_MEMBER_ADD",
+    "GUILD_MEMBER_REMOVE",
+    "GUILD_MEMBER_UPDATE",
+    "GUILD_ROLE_CREATE",
+    "GUILD_ROLE_DELETE",
+    "GUILD_ROLE_UPDATE",
+    "GUILD_STICKERS_UPDATE",
+    "GUILD_UPDATE",
+    "INTEGRATION_CREATE",
+    "INTEGRATION_DELETE",
+    "INTEGRATION_UPDATE",
+    "INTERACTION_CREATE",
+    "INVITE_CREATE",
+    "INVITE_DELETE",
+    "MESSAGE_CREATE",
+    "MESSAGE_DELETE",
+    "MESSAGE_DELETE_BULK",
+    "MESSAGE_REACTION_ADD",
+    "MESSAGE_REACTION_REMOVE",
+    "MESSAGE_REACTION_REMOVE_EMOJI",
+    "MESSAGE_REACTION_REMOVE_ALL",
+    "MESSAGE_UPDATE",
+    "PRESENCE_UPDATE",
+    "PRESENCES_REPLACE",
+    "READY",
+    "RESUMED",
+    "STAGE_INSTANCE_CREATE",
+    "STAGE_INSTANCE_DELETE",
+    "STAGE_INSTANCE_UPDATE",
+    "THREAD_CREATE",
+    "THREAD_DELETE",
+    "THREAD_LIST_SYNC",
+    "THREAD_MEMBER_UPDATE",
+    "THREAD_MEMBERS_UPDATE",
+    "THREAD_UPDATE",
+    "TYPING_START",
+    "USER_UPDATE",
+    "VOICE_SERVER_UPDATE",
+    "VOICE_STATE_UPDATE",
+    "WEBHOOKS_UPDATE",
+];
+
+/// Names of the opcodes that clients send.
+const OPCODES: &[(&str, OpCode)] = &[
+    ("heartbeat", OpCode::Heartbeat),
+    ("identify", OpCode::Identify),
+    ("presence_update", OpCode::PresenceUpdate),
+    ("voice_state_update", OpCode::VoiceStateUpdate),
+    ("resume", OpCode::Resume),
+    ("request_guild_members", OpCode::RequestGuildMembers),
+];
+
+const INTENTS: &[(&str, Intents)] = &[
+    ("GUILDS", Intents::GUILDS),
+    ("GUILD_MEMBERS", Intents::GUILD_MEMBERS),
+    ("GUILD_MODERATION", Intents::GUILD_MODERATION),
+    (
+        "GUILD_EMOJIS_AND_STICKERS",
+        Intents::GUILD_EMOJIS_AND_STICKERS,
+    ),
+    ("GUILD_INTEGRATIONS", Intents::GUILD_INTEGRATIONS),
+    ("GUILD_WEBHOOKS", Intents::GUILD_WEBHOOKS),
+    ("GUILD_INVITES", Intents::GUILD_INVITES),
+    ("GUILD_VOICE_STATES", Intents::GUILD_VOICE_STATES),
+    ("GUILD_PRESENCES", Intents::GUILD_PRESENCES),
+    ("GUILD_MESSAGES", Intents::GUILD_MESSAGES),
+    ("GUILD_MESSAGE_REACTIONS", Intents::GUILD_MESSAGE_REACTIONS),
+    ("GUILD_MESSAGE_TYPING", Intents::GUILD_MESSAGE_TYPING),
+    ("DIRECT_MESSAGES", Intents::DIRECT_MESSAGES),
+    (
+        "DIRECT_MESSAGE_REACTIONS",
+        Intents::DIRECT_MESSAGE_REACTIONS,
+    ),
+    ("DIRECT_MESSAGE_TYPING", Intents::DIRECT_MESSAGE_TYPING),
+    ("MESSAGE_CONTENT", Intents::MESSAGE_CONTENT),
+    ("GUILD_SCHEDULED_EVENTS", Intents::GUILD_SCHEDULED_EVENTS),
+    (
+        "AUTO_MODERATION_CONFIGURATION",
+        Intents::AUTO_MODERATION_CONFIGURATION,
+    ),
+    (
+        "AUTO_MODERATION_EXECUTION",
+        Intents::AUTO_MODERATION_EXECUTION,
+    ),
+];
+
+pub enum ParseError {
+    InvalidAction,
+    InvalidBlock,
+    ExpectedBoolean,
+    ExpectedInteger,
+    MissingRequiredArgument,
+    UnexpectedArgument,
+    InvalidJson,
+    UnclosedBlock,
+    UnexpectedClosingBrace,
+    ExpectedDuration,
+    InvalidOpcode,
+    UnknownPlaceholder,
+    InvalidCondition,
+    InvalidIntent,
+    UnexpectedBody,
+    ExpectedProbability,
+    FileNotFound,
+    RecursiveInclude,
+    InvalidTrigger,
+    NestedHandler,
+    UnknownEvent,
+    InvalidPayload,
+    EmptyLoop,
+    InvalidCloseCode,
+    CloseReasonTooLong,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidAction => f.write_str("Invalid action"),
+            Self::InvalidBlock => f.write_str("Invalid block"),
+            Self::ExpectedBoolean => f.write_str("Expected a boolean"),
+            Self::ExpectedInteger => f.write_str("Expected an integer"),
+            Self::MissingRequiredArgument => f.write_str("Missing required argument"),
+            Self::UnexpectedArgument => f.write_str("Unexpected argument"),
+            Self::InvalidJson => f.write_str("Invalid JSON"),
+            Self::UnclosedBlock => f.write_str("Block is missing a closing brace"),
+            Self::UnexpectedClosingBrace => f.write_str("Closing brace without a block"),
+            Self::ExpectedDuration => f.write_str("Expected a duration like 500ms, 5s or 1m"),
+            Self::InvalidOpcode => f.write_str("Expected an opcode sent by clients"),
+            Self::UnknownPlaceholder => f.write_str("Unknown placeholder in payload"),
+            Self::InvalidCondition => f.write_str(
+                "Expected a condition like shard == 0, intents has GUILD_MEMBERS or resumed",
+            ),
+            Self::InvalidIntent => f.write_str("Unknown intent"),
+            Self::UnexpectedBody => f.write_str("Only parallel blocks have multiple bodies"),
+            Self::ExpectedProbability => f.write_str("Expected a probability between 0 and 1"),
+            Self::FileNotFound => f.write_str("File not found or access denied"),
+            Self::RecursiveInclude => f.write_str("Script includes itself"),
+            Self::InvalidTrigger => {
+                f.write_str("Expected a trigger like identify, heartbeat 3, 8 or close 4000")
+            }
+            Self::NestedHandler => f.write_str("Handlers are only allowed at the top level"),
+            Self::UnknownEvent => f.write_str("Unknown dispatch event"),
+            Self::InvalidPayload => f.write_str("Payload does not match the event"),
+            Self::EmptyLoop => f.write_str("Loop without actions would never stop"),
+            Self::InvalidCloseCode => f.write_str("Close code is not allowed in close frames"),
+            Self::CloseReasonTooLong => f.write_str("Close reason is longer than 123 bytes"),
+        }
+    }
+}
+
+impl ParseError {
+    /// The error about the token, which must be a slice of the line.
+    fn at(self, token: &str) -> Spanned<'_> {
+        Spanned {
+            kind: self,
+            token,
+            suggestion: None,
+            note: None,
+        }
+    }
+}
+
+/// A parse error and the part of the line it is about.
+pub struct Spanned<'a> {
+    kind: ParseError,
+    token: &'a str,
+    suggestion: Option<&'static str>,
+    note: Option<String>,
+}
+
+impl Spanned<'_> {
+    /// Suggests the candidate that is closest to the token, if any is close.
+    fn suggest<'c>(mut self, candidates: impl IntoIterator<Item = &'c &'static str>) -> Self {
+        self.suggestion = candidates
+            .into_iter()
+            .map(|candidate| (distance(self.token, candidate), *candidate))
+            .filter(|(distance, candidate)| {
+                *distance > 0 && *distance <= (candidate.len().max(self.token.len()) / 3).max(1)
+            })
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate);
+
+        self
+    }
+
+    fn note(mut self, note: impl Display) -> Self {
+        self.note = Some(note.to_string());
+        self
+    }
+}
+
+/// The empty slice after the token, for errors about missing arguments.
+fn end(token: &str) -> &str {
+    &token[token.len()..]
+}
+
+/// Levenshtein distance between the strings.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// A parse error with its location in the script.
+pub struct ScriptError {
+    kind: ParseError,
+    path: String,
+    /// Line number, starting at 1.
+    line: usize,
+    /// Column in characters, starting at 1.
+    column: usize,
+    token: String,
+    source: String,
+    suggestion: Option<&'static str>,
+    note: Option<String>,
+}
+
+impl ScriptError {
+    /// The error in the logical line starting at the given index, which
+    /// spans several lines if it contains a multi-line payload.
+    fn new(path: &str, index: usize, text: &str, error: Spanned<'_>) -> Box<Self> {
+        // Tokens are slices of the text, which gives their position
+        let offset = (error.token.as_ptr() as usize)
+            .checked_sub(text.as_ptr() as usize)
+            .filter(|offset| offset + error.token.len() <= text.len())
+            .unwrap_or_default();
+
+        let before = &text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        let source = text[line_start..].lines().next().unwrap_or_default();
+
+        // Only mark the part of the token on the first line
+        let token = error.token.lines().next().unwrap_or_default();
+
+        Box::new(Self {
+            kind: error.kind,
+            path: path.to_string(),
+            line: index + before.matches('\n').count() + 1,
+            column: text[line_start..offset].chars().count() + 1,
+            token: token.to_string(),
+            source: source.to_string(),
+            suggestion: error.suggestion,
+            note: error.note,
+        })
+    }
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+        let indent = " ".repeat(self.column - 1);
+        let marker = "^".repeat(self.token.chars().count().max(1));
+
+        // Braces are clear from the snippet alone
+        if !self.token.contains(char::is_alphanumeric) {
+            writeln!(f, "error: {}", self.kind)?;
+        } else {
+            writeln!(f, "error: {} `{}`", self.kind, self.token)?;
+        }
+
+        writeln!(f, "{gutter}--> {}:{}:{}", self.path, self.line, self.column)?;
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{} | {}", self.line, self.source)?;
+        write!(f, "{gutter} | {indent}{marker}")?;
+
+        if let Some(note) = &self.note {
+            write!(f, "\n{gutter} = note: {note}")?;
+        }
+
+        if let Some(suggestion) = self.suggestion {
+            write!(f, "\n{gutter} = help: did you mean `{suggestion}`?")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses durations like `500ms`, `5s` or `1m`.
+fn parse_duration(input: &str) -> Result<Duration, Spanned<'_>> {
+    let error = || ParseError::ExpectedDuration.at(input);
+
+    let (value, unit) = input
+        .find(|c: char| !c.is_ascii_digit())
+        .map(|index| input.split_at(index))
+        .ok_or_else(error)?;
+    let value: u64 = value.parse().map_err(|_| error())?;
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(value)),
+        "s" => Ok(Duration::from_secs(value)),
+        "m" => value
+            .checked_mul(60)
+            .map(Duration::from_secs)
+            .ok_or_else(|| error().note("the duration is too long")),
+        _ => Err(error()),
+    }
+}
+
+/// Parses opcodes sent by clients, by number or name.
+fn parse_opcode(input: &str) -> Result<OpCode, Spanned<'_>> {
+    let name = input.to_ascii_lowercase();
+
+    if let Some((_, op)) = OPCODES.iter().find(|(opcode, _)| *opcode == name) {
+        return Ok(*op);
+    }
+
+    input
+        .parse()
+        .ok()
+        .and_then(OpCode::from)
+        .filter(|op| OPCODES.iter().any(|(_, opcode)| opcode == op))
+        .ok_or_else(|| {
+            ParseError::InvalidOpcode
+                .at(input)
+                .suggest(OPCODES.iter().map(|(name, _)| name))
+        })
+}
+
+/// Parses intents by name, like `GUILD_MEMBERS`, or by value.
+fn parse_intent(input: &str) -> Result<Intents, Spanned<'_>> {
+    if let Some((_, intent)) = INTENTS.iter().find(|(name, _)| *name == input) {
+        return Ok(*intent);
+    }
+
+    input
+        .parse()
+        .ok()
+        .and_then(Intents::from_bits)
+        .ok_or_else(|| {
+            ParseError::InvalidIntent
+                .at(input)
+                .suggest(INTENTS.iter().map(|(name, _)| name))
+        })
+}
+
+/// Parses probabilities like `0.1`.
+fn parse_probability(input: &str) -> Result<f64, Spanned<'_>> {
+    input
+        .parse()
+        .ok()
+        .filter(|probability| (0.0..=1.0).contains(probability))
+        .ok_or_else(|| ParseError::ExpectedProbability.at(input))
+}
+
+fn parse_integer<T: std::str::FromStr>(input: &str) -> Result<T, Spanned<'_>> {
+    input
+        .parse()
+        .map_err(|_| ParseError::ExpectedInteger.at(input))
+}
+
+/// Parses a JSON payload with placeholders.
+fn parse_payload(input: &str) -> Result<OwnedValue, Spanned<'_>> {
+    let mut data = input.to_string();
+    let data: OwnedValue = unsafe { simd_json::from_str(&mut data) }
+        .map_err(|e| ParseError::InvalidJson.at(input).note(e))?;
+
+    template::validate(&data).map_err(|name| {
+        let token = input
+            .find(&name)
+            .map_or(input, |index| &input[index..index + name.len()]);
+
+        ParseError::UnknownPlaceholder
+            .at(token)
+            .suggest(template::PLACEHOLDERS)
+    })?;
+
+    Ok(data)
+}
+
+fn parse_condition(input: &str) -> Result<Condition, Spanned<'_>> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+
+    match words[..] {
+        ["shard", "==", number] => Ok(Condition::Shard(parse_integer(number)?)),
+        ["intents", "has", intent] => Ok(Condition::IntentsHas(parse_intent(intent)?)),
+        ["resumed"] => Ok(Condition::Resumed),
+        _ => Err(ParseError::InvalidCondition.at(input)),
+    }
+}
+
+/// Parses triggers like `heartbeat`, `heartbeat 3` or `close 4000`.
+fn parse_trigger(input: &str) -> Result<Trigger, Spanned<'_>> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+
+    match words[..] {
+        ["close", code] => Ok(Trigger::Close(parse_integer(code)?)),
+        [op] => Ok(Trigger::Op(parse_opcode(op)?, None)),
+        [op, nth] => Ok(Trigger::Op(parse_opcode(op)?, Some(parse_integer(nth)?))),
+        _ => Err(ParseError::InvalidTrigger.at(input)),
+    }
+}
+
+/// Checks the payload against the twilight-model type of the event, with
+/// example values for placeholders.
+fn check_dispatch<'a>(
+    event_type: &'a str,
+    payload: &'a str,
+    data: &OwnedValue,
+) -> Result<(), Spanned<'a>> {
+    if !EVENT_TYPES.contains(&event_type) {
+        return Err(ParseError::UnknownEvent.at(event_type).suggest(EVENT_TYPES));
+    }
+
+    DispatchEventWithTypeDeserializer::new(event_type)
+        .deserialize(&template::example(data))
+        .map(|_| ())
+        .map_err(|e| ParseError::InvalidPayload.at(payload).note(e))
+}
+
+impl<'a> TryFrom<(&'a str, Option<&'a str>)> for Action {
+    type Error = Spanned<'a>;
+
+    fn try_from((action_name, arguments): (&'a str, Option<&'a str>)) -> Result<Self, Self::Error> {
+        let required =
+            || arguments.ok_or_else(|| ParseError::MissingRequiredArgument.at(end(action_name)));
+        let without_arguments = |action| match arguments {
+            Some(arguments) if arguments.starts_with('#') => Err(ParseError::UnexpectedArgument
+                .at(arguments)
+                .note("comments must be on their own line")),
+            Some(arguments) => Err(ParseError::UnexpectedArgument.at(arguments)),
+            None => Ok(action),
+        };
+
+        match action_name {
+            "sleep_ms" => Ok(Self::Sleep(Duration::from_millis(parse_integer(
+                required()?,
+            )?))),
+            "sleep_s" => Ok(Self::Sleep(Duration::from_secs(
+                parse_integer(required()?)?,
+            ))),
+            "sleep_rand" => {
+                let arguments = required()?;
+                let (min, max) = arguments
+                    .split_once("..")
+                    .ok_or_else(|| ParseError::ExpectedDuration.at(arguments))?;
+                let (min, max) = (parse_duration(min.trim())?, parse_duration(max.trim())?);
+
+                if min > max {
+                    return Err(ParseError::ExpectedDuration
+                        .at(arguments)
+                        .note("the minimum is larger than the maximum"));
+                }
+
+                Ok(Self::SleepRand(min..=max))
+            }
+            "invalidate_session" => {
+                let arguments = required()?;
+
+                Ok(Self::InvalidateSession(
+                    arguments
+                        .parse()
+                        .map_err(|_| ParseError::ExpectedBoolean.at(arguments))?,
+                ))
+            }
+            "dispatch" => {
+                let arguments = required()?;
+                let (event_type, data) = arguments
+                    .split_once(' ')
+                    .ok_or_else(|| ParseError::MissingRequiredArgument.at(end(arguments)))?;
+
+                Ok(Self::Dispatch {
+                    event_type: event_type.to_string(),
+                    data: parse_payload(data.trim())?,
+                })
+            }
+            "heartbeat" => without_arguments(Self::Heartbeat),
+            "reconnect" => Ok(Self::Reconnect(arguments.map(parse_duration).transpose()?)),
+            "random_message_create" => without_arguments(Self::RandomMessageCreate),
+            "random_guild_create" => without_arguments(Self::RandomGuildCreate),
+            "burst" => {
+                let arguments = required()?;
+                let mut parts = arguments.splitn(4, ' ');
+                let event_type = parts.next().unwrap_or_default();
+                let missing = || ParseError::MissingRequiredArgument.at(end(arguments));
+
+                let rate = parts.next().ok_or_else(missing)?;
+                let rate_token = rate
+                    .strip_prefix("rate=")
+                    .and_then(|rate| rate.strip_suffix("/s"))
+                    .ok_or_else(|| {
+                        ParseError::ExpectedInteger
+                            .at(rate)
+                            .note("expected rate=<n>/s")
+                    })?;
+                let rate = parse_integer(rate_token)?;
+
+                if rate == 0 {
+                    return Err(ParseError::ExpectedInteger
+                        .at(rate_token)
+                        .note("the rate must be positive"));
+                }
+
+                // The interval between events can't be shorter than 1ns
+                if rate > MAX_BURST_RATE {
+                    return Err(ParseError::ExpectedInteger
+                        .at(rate_token)
+                        .note(format_args!("the rate must be at most {MAX_BURST_RATE}/s")));
+                }
+
+                let duration = parts.next().ok_or_else(missing)?;
+                let duration =
+                    parse_duration(duration.strip_prefix("duration=").ok_or_else(|| {
+                        ParseError::ExpectedDuration
+                            .at(duration)
+                            .note("expected duration=<duration>")
+                    })?)?;
+
+                let data = match parts.next() {
+                    Some(data) => Some(parse_payload(data.trim())?),
+                    None if event_type == "MESSAGE_CREATE" => None,
+                    None => {
+                        return Err(missing().note(
+                            "only MESSAGE_CREATE can be generated, other events need a payload",
+                        ))
+                    }
+                };
+
+                Ok(Self::Burst {
+                    event_type: event_type.to_string(),
+                    rate,
+                    duration,
+                    data,
+                })
+            }
+            "graceful_close" => {
+                let (code_token, reason) = match arguments {
+                    Some(arguments) => match arguments.split_once(' ') {
+                        Some((code, reason)) => (code, reason),
+                        None => (arguments, ""),
+                    },
+                    None => ("1000", ""),
+                };
+                let code = parse_integer(code_token)?;
+
+                // Clients fail the connection on close frames with these
+                if !CloseCode::from(code).is_allowed() {
+                    return Err(ParseError::InvalidCloseCode
+                        .at(code_token)
+                        .note("allowed codes are 1000 to 1003, 1007 to 1013 and 3000 to 4999"));
+                }
+
+                if reason.len() > MAX_CLOSE_REASON_LEN {
+                    return Err(ParseError::CloseReasonTooLong
+                        .at(reason)
+                        .note(format_args!("the reason is {} bytes long", reason.len())));
+                }
+
+                Ok(Self::GracefulClose {
+                    code,
+                    reason: reason.to_string(),
+                })
+            }
+            "abrupt_close" => without_arguments(Self::AbruptClose),
+            "wait_ready" => without_arguments(Self::WaitReady),
+            "expect" => {
+                let arguments = required()?;
+                let missing = || ParseError::MissingRequiredArgument.at(end(arguments));
+                let mut words = arguments.split_whitespace();
+
+                let op = parse_opcode(words.next().ok_or_else(missing)?)?;
+
+                match words.next() {
+                    Some("within") => {}
+                    Some(word) => {
+                        return Err(ParseError::UnexpectedArgument.at(word).suggest(&["within"]))
+                    }
+                    None => return Err(missing().note("expected within <duration>")),
+                }
+
+                let within = parse_duration(words.next().ok_or_else(missing)?)?;
+
+                let field = match words.next() {
+                    Some("with") => Some(words.next().ok_or_else(missing)?.to_string()),
+                    Some(word) => {
+                        return Err(ParseError::UnexpectedArgument.at(word).suggest(&["with"]))
+                    }
+                    None => None,
+                };
+
+                if let Some(word) = words.next() {
+                    return Err(ParseError::UnexpectedArgument.at(word));
+                }
+
+                Ok(Self::Expect { op, within, field })
+            }
+            _ => Err(ParseError::InvalidAction
+                .at(action_name)
+                .suggest(ACTIONS.iter().chain(BLOCKS))),
+        }
+    }
+}
+
+/// Parses the action. Problems with dispatch payloads are added to the
+/// diagnostics if the context checks them, unless the action is prefixed
+/// with `unchecked`.
+fn parse_action<'a>(
+    context: &Context<'_>,
+    line: &'a str,
+    diagnostics: &mut Vec<Spanned<'a>>,
+) -> Result<Action, Spanned<'a>> {
+    let (action_name, arguments) = match line.split_once(char::is_whitespace) {
+        Some((action_name, arguments)) => (action_name, Some(arguments.trim())),
+        None => (line, None),
+    };
+
+    // Payloads that don't match their event on purpose are not checked
+    if action_name == "unchecked" {
+        let action = arguments.ok_or_else(|| ParseError::MissingRequiredArgument.at(end(line)))?;
+
+        return parse_action(context, action, &mut Vec::new());
+    }
+
+    // `chance P <action>` is short for a block with a single action
+    if action_name == "chance" {
+        let arguments =
+            arguments.ok_or_else(|| ParseError::MissingRequiredArgument.at(end(line)))?;
+        let (probability, action) = arguments
+            .split_once(' ')
+            .ok_or_else(|| ParseError::MissingRequiredArgument.at(end(arguments)))?;
+
+        return Ok(Action::Chance(
+            parse_probability(probability)?,
+            vec![parse_action(context, action.trim_start(), diagnostics)?],
+        ));
+    }
+
+    // Payloads from files are read relative to the script
+    if action_name == "dispatch_file" {
+        let arguments =
+            arguments.ok_or_else(|| ParseError::MissingRequiredArgument.at(end(line)))?;
+        let (event_type, file) = arguments
+            .split_once(' ')
+            .ok_or_else(|| ParseError::MissingRequiredArgument.at(end(arguments)))?;
+        let file = file.trim();
+
+        let mut content = fs::read_to_string(context.resolve(file))
+            .map_err(|e| ParseError::FileNotFound.at(file).note(e))?;
+        let data: OwnedValue = unsafe { simd_json::from_str(&mut content) }
+            .map_err(|e| ParseError::InvalidJson.at(file).note(e))?;
+
+        template::validate(&data).map_err(|name| {
+            ParseError::UnknownPlaceholder
+                .at(file)
+                .note(format_args!("{{{{{name}}}}} is not a placeholder"))
+        })?;
+
+        if context.checks_payloads() {
+            diagnostics.extend(check_dispatch(event_type, file, &data).err());
+        }
+
+        return Ok(Action::Dispatch {
+            event_type: event_type.to_string(),
+            data,
+        });
+    }
+
+    let action = Action::try_from((action_name, arguments))?;
+
+    // The event type is the first argument and the payload the last
+    if let (
+        Action::Dispatch { event_type, data }
+        | Action::Burst {
+            event_type,
+            data: Some(data),
+            ..
+        },
+        Some(arguments),
+        true,
+    ) = (&action, arguments, context.checks_payloads())
+    {
+        let payload = arguments
+            .find(['{', '['])
+            .map_or(arguments, |index| &arguments[index..]);
+
+        diagnostics.extend(check_dispatch(&arguments[..event_type.len()], payload, data).err());
+    }
+
+    Ok(action)
+}
+
+enum Block {
+    Repeat(u64),
+    Loop,
+    If(Condition),
+    Chance(f64),
+    Parallel,
+    Spawn,
+    On(Trigger),
+}
+
+/// Parses block headers like `repeat N`, without the opening brace.
+fn parse_block_header(header: &str) -> Result<Block, Spanned<'_>> {
+    let (keyword, argument) = match header.split_once(' ') {
+        Some((keyword, argument)) => (keyword, Some(argument.trim())),
+        None => (header, None),
+    };
+
+    match (keyword, argument) {
+        ("repeat", Some(count)) => Ok(Block::Repeat(parse_integer(count)?)),
+        ("if", Some(condition)) => Ok(Block::If(parse_condition(condition)?)),
+        ("chance", Some(probability)) => Ok(Block::Chance(parse_probability(probability)?)),
+        ("loop", None) => Ok(Block::Loop),
+        ("parallel", None) => Ok(Block::Parallel),
+        ("spawn", None) => Ok(Block::Spawn),
+        ("on", Some(trigger)) => Ok(Block::On(parse_trigger(trigger)?)),
+        ("repeat" | "if" | "chance" | "on", None) => {
+            Err(ParseError::MissingRequiredArgument.at(end(keyword)))
+        }
+        ("loop" | "parallel" | "spawn", Some(argument)) => {
+            Err(ParseError::UnexpectedArgument.at(argument))
+        }
+        _ => Err(ParseError::InvalidBlock.at(keyword).suggest(BLOCKS)),
+    }
+}
+
+/// The file being parsed.
+struct Context<'p> {
+    path: &'p str,
+    /// This file and the files that include it, to detect include cycles.
+    parents: &'p [PathBuf],
+    /// Whether the file is included in a block.
+    nested: bool,
+    /// Where problems with dispatch payloads go, if they are checked.
+    diagnostics: Option<&'p RefCell<Vec<ScriptError>>>,
+    /// Whether problems with dispatch payloads are errors.
+    strict: bool,
+}
+
+impl Context<'_> {
+    fn checks_payloads(&self) -> bool {
+        self.strict || self.diagnostics.is_some()
+    }
+
+    /// Path of a file that is referenced relative to this one.
+    fn resolve(&self, file: &str) -> PathBuf {
+        Path::new(self.path)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(file)
+    }
+}
+
+/// Whether the line starts a block, like `repeat N {`.
+fn is_block_header(line: &str) -> bool {
+    let Some(header) = line.strip_suffix('{') else {
+        return false;
+    };
+
+    let words: Vec<&str> = header.split_whitespace().collect();
+
+    match words[..] {
+        ["chance", _] => true,
+        [keyword, ..] => matches!(
+            keyword,
+            "repeat" | "loop" | "if" | "parallel" | "spawn" | "on"
+        ),
+        [] => false,
+    }
+}
+
+/// How many more brackets the line opens than it closes, ignoring those in
+/// JSON strings.
+fn bracket_depth(line: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in line.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' | '[' if !in_string => depth += 1,
+            '}' | ']' if !in_string => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth
+}
+
+/// The logical lines of a script with their index. Blank lines and `#`
+/// comments are skipped, and payloads that span several lines are joined.
+fn logical_lines(input: &str) -> impl Iterator<Item = (usize, Cow<'_, str>)> {
+    let mut lines = input.lines().enumerate();
+
+    std::iter::from_fn(move || loop {
+        let (index, line) = lines.next()?;
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let mut depth = bracket_depth(line);
+
+        if depth <= 0 || is_block_header(trimmed) {
+            return Some((index, Cow::Borrowed(line)));
+        }
+
+        let mut text = line.to_string();
+
+        while depth > 0 {
+            let Some((_, line)) = lines.next() else {
+                break;
+            };
+
+            text.push('\n');
+            text.push_str(line);
+            depth += bracket_depth(line);
+        }
+
+        return Some((index, Cow::Owned(text)));
+    })
+}
+
+/// Parses the script. The path is used for errors and to resolve includes.
+/// In strict mode, dispatch payloads that twilight-model cannot deserialize
+/// as their event are errors.
+pub fn parse(path: &str, input: &str, strict: bool) -> Result<Vec<Action>, Box<ScriptError>> {
+    parse_root(path, input, None, strict)
+}
+
+/// Parses the script and checks dispatch payloads against the twilight-model
+/// types. Returns all problems that were found.
+pub fn check(path: &str, input: &str) -> Vec<ScriptError> {
+    let diagnostics = RefCell::new(Vec::new());
+    let result = parse_root(path, input, Some(&diagnostics), false);
+    let mut diagnostics = diagnostics.into_inner();
+
+    if let Err(err) = result {
+        diagnostics.push(*err);
+    }
+
+    diagnostics
+}
+
+fn parse_root(
+    path: &str,
+    input: &str,
+    diagnostics: Option<&RefCell<Vec<ScriptError>>>,
+    strict: bool,
+) -> Result<Vec<Action>, Box<ScriptError>> {
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+
+    parse_source(
+        &Context {
+            path,
+            parents: &[canonical],
+            nested: false,
+            diagnostics,
+            strict,
+        },
+        input,
+    )
+}
+
+fn parse_source(context: &Context<'_>, input: &str) -> Result<Vec<Action>, Box<ScriptError>> {
+    parse_block(context, &mut logical_lines(input), None).map(|(actions, _)| actions)
+}
+
+/// Parses actions until the end of the input, or until the closing brace if
+/// nested in the block that starts on the given line. Also returns whether
+/// the closing brace opened another body with `} {`, which only parallel
+/// blocks have.
+fn parse_block<'a>(
+    context: &Context<'_>,
+    lines: &mut impl Iterator<Item = (usize, Cow<'a, str>)>,
+    header: Option<(usize, &str)>,
+) -> Result<(Vec<Action>, bool), Box<ScriptError>> {
+    let mut actions = Vec::new();
+
+    while let Some((index, source)) = lines.next() {
+        let source = &*source;
+        let located = |error| ScriptError::new(context.path, index, source, error);
+        let line = source.trim();
+
+        if line == "}" || line == "} {" {
+            if header.is_some() {
+                return Ok((actions, line == "} {"));
+            }
+
+            return Err(located(ParseError::UnexpectedClosingBrace.at(line)));
+        }
+
+        // Blocks start with `repeat N {`, `loop {`, `if <condition> {`,
+        // `chance P {`, `parallel {`, `spawn {` or `on <trigger> {`
+        if is_block_header(line) {
+            let block_header = line[..line.len() - 1].trim_end();
+            let block = parse_block_header(block_header).map_err(located)?;
+
+            if matches!(block, Block::On(_)) && (context.nested || header.is_some()) {
+                return Err(located(ParseError::NestedHandler.at(block_header)));
+            }
+            let (body, mut continued) = parse_block(context, lines, Some((index, source)))?;
+
+            let action = match block {
+                Block::Repeat(count) => Action::Repeat(count, body),
+                Block::Loop if body.is_empty() => {
+                    return Err(located(ParseError::EmptyLoop.at(block_header)));
+                }
+                Block::Loop => Action::Loop(body),
+                Block::If(condition) => Action::If(condition, body),
+                Block::Chance(probability) => Action::Chance(probability, body),
+                Block::Parallel => {
+                    let mut bodies = vec![body];
+
+                    while continued {
+                        let (body, next) = parse_block(context, lines, Some((index, source)))?;
+                        bodies.push(body);
+                        continued = next;
+                    }
+
+                    Action::Parallel(bodies)
+                }
+                // The rest of the enclosing block runs alongside the spawned
+                // actions
+                Block::Spawn if !continued => {
+                    let (rest, rest_continued) = parse_block(context, lines, header)?;
+
+                    // Handlers after a spawn are still at the top level
+                    let (handlers, rest): (Vec<_>, Vec<_>) = rest
+                        .into_iter()
+                        .partition(|action| matches!(action, Action::On(..)));
+                    actions.extend(handlers);
+                    actions.push(Action::Parallel(vec![body, rest]));
+
+                    return Ok((actions, rest_continued));
+                }
+                Block::Spawn => Action::Parallel(vec![body]),
+                Block::On(trigger) => Action::On(trigger, body),
+            };
+
+            if continued {
+                return Err(located(ParseError::UnexpectedBody.at(line)));
+            }
+
+            actions.push(action);
+            continue;
+        }
+
+        // Included scripts are inserted in place
+        if let Some(file) = line.strip_prefix("include ") {
+            let file = file.trim();
+            let path = context.resolve(file);
+            let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+
+            if context.parents.contains(&canonical) {
+                return Err(located(ParseError::RecursiveInclude.at(file)));
+            }
+
+            let content = fs::read_to_string(&path)
+                .map_err(|e| located(ParseError::FileNotFound.at(file).note(e)))?;
+
+            let mut parents = context.parents.to_vec();
+            parents.push(canonical);
+
+            actions.extend(parse_source(
+                &Context {
+                    path: &path.to_string_lossy(),
+                    parents: &parents,
+                    nested: context.nested || header.is_some(),
+                    diagnostics: context.diagnostics,
+                    strict: context.strict,
+                },
+                &content,
+            )?);
+            continue;
+        }
+
+        let mut diagnostics = Vec::new();
+        actions.push(parse_action(context, line, &mut diagnostics).map_err(located)?);
+
+        if context.strict {
+            if let Some(error) = diagnostics.pop() {
+                return Err(located(error));
+            }
+        } else if let Some(collected) = context.diagnostics {
+            collected
+                .borrow_mut()
+                .extend(diagnostics.into_iter().map(|error| *located(error)));
+        }
+    }
+
+    match header {
+        Some((index, source)) => {
+            let header = source.trim_end();
+            let brace = &header[header.len() - 1..];
+
+            Err(ScriptError::new(
+                context.path,
+                index,
+                source,
+                ParseError::UnclosedBlock.at(brace),
+            ))
+        }
+        None => Ok((actions, false)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use super::{bracket_depth, parse, ParseError};
+    use crate::script::Action;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("mock-gateway-{name}-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn parse_ok(input: &str) -> Vec<Action> {
+        parse("test", input, false).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    #[test]
+    fn multi_line_payload_in_block() {
+        let input = "repeat 2 {\n    dispatch TEST {\n        \"a\": 1\n    }\n}\n";
+        let actions = parse_ok(input);
+
+        assert!(matches!(
+            actions.as_slice(),
+            [Action::Repeat(2, body)]
+                if matches!(body.as_slice(), [Action::Dispatch { event_type, .. }] if event_type == "TEST")
+        ));
+    }
+
+    #[test]
+    fn braces_in_json_strings() {
+        assert_eq!(bracket_depth(r#"{"a": "} { ]", "b": "\"{"}"#), 0);
+
+        let input = "dispatch TEST {\n    \"a\": \"}\",\n    \"b\": \"{{\"\n}\nheartbeat\n";
+        let actions = parse_ok(input);
+
+        assert!(matches!(
+            actions.as_slice(),
+            [Action::Dispatch { .. }, Action::Heartbeat]
+        ));
+    }
+
+    #[test]
+    fn block_continuation() {
+        let input = "parallel {\n    heartbeat\n} {\n    heartbeat\n    heartbeat\n}\n";
+        let actions = parse_ok(input);
+
+        assert!(matches!(
+            actions.as_slice(),
+            [Action::Parallel(bodies)] if bodies.len() == 2 && bodies[1].len() == 2
+        ));
+
+        let error = parse("test", "repeat 2 {\n    heartbeat\n} {\n}\n", false).unwrap_err();
+        assert!(matches!(error.kind, ParseError::UnexpectedBody));
+        assert_eq!(error.line, 1);
+    }
+
+    #[test]
+    fn unclosed_payload() {
+        let error = parse("test", "dispatch TEST {\n    \"a\": 1\nheartbeat\n", false).unwrap_err();
+
+        assert!(matches!(error.kind, ParseError::InvalidJson));
+        assert_eq!(error.line, 1);
+    }
+
+    #[test]
+    fn spawn_followed_by_handlers() {
+        let input = "spawn {\n    heartbeat\n}\non heartbeat {\n    heartbeat\n}\nheartbeat\n";
+        let actions = parse_ok(input);
+
+        let [Action::On(_, _), Action::Parallel(bodies)] = actions.as_slice() else {
+            panic!("handler was not hoisted out of the spawn: {actions:?}");
+        };
+        assert!(matches!(bodies[0].as_slice(), [Action::Heartbeat]));
+        assert!(matches!(bodies[1].as_slice(), [Action::Heartbeat]));
+
+        let error = parse("test", "repeat 2 {\n    on heartbeat {\n    }\n}\n", false).unwrap_err();
+        assert!(matches!(error.kind, ParseError::NestedHandler));
+    }
+
+    #[test]
+    fn unexpected_arguments() {
+        for line in ["wait_ready 5s", "abrupt_close now", "heartbeat # comment"] {
+            let error = parse("test", line, false).unwrap_err();
+            assert!(
+                matches!(error.kind, ParseError::UnexpectedArgument),
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn include_cycles() {
+        let dir = temp_dir("include-cycles");
+        let a = dir.join("a.txt");
+        let b = dir.join("b.txt");
+        fs::write(&a, "heartbeat\ninclude b.txt\n").unwrap();
+        fs::write(&b, "include a.txt\n").unwrap();
+        let own = dir.join("self.txt");
+        fs::write(&own, "include self.txt\n").unwrap();
+
+        for path in [&a, &own] {
+            let path = path.to_str().unwrap();
+            let input = fs::read_to_string(path).unwrap();
+            let error = parse(path, &input, false).unwrap_err();
+            assert!(matches!(error.kind, ParseError::RecursiveInclude), "{path}");
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -4,7 +4,12 @@ use std::{
 };
 
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
-use twilight_model::gateway::{payload::outgoing::identify::IdentifyInfo, Intents, ShardId};
+use twilight_model::{
+    gateway::{payload::outgoing::identify::IdentifyInfo, Intents, ShardId},
+    id::{marker::GuildMarker, Id},
+};
+
+use crate::{handler::WriteHandle, world::shard_for};
 
 type SessionId = String;
 
@@ -16,8 +21,8 @@ impl Sessions {
         Self(Arc::new(Mutex::new(HashMap::new())))
     }
 
-    pub fn create_session(&self, identify: &IdentifyInfo) -> SessionId {
-        let session = Session::from(identify);
+    pub fn create_session(&self, identify: &IdentifyInfo, writer: WriteHandle) -> SessionId {
+        let session = Session::new(identify, writer);
 
         // Session IDs are 32 bytes of ASCII
         let mut rng = thread_rng();
@@ -50,6 +55,30 @@ impl Sessions {
             .contains_key(session_id)
     }
 
+    /// Sends the session's events to the connection that resumed it.
+    pub fn resume_session(&self, session_id: &SessionId, writer: WriteHandle) {
+        if let Some(session) = self
+            .0
+            .lock()
+            .expect("Sessions mutex poisoned")
+            .get_mut(session_id)
+        {
+            session.writer = writer;
+            session.resumed = true;
+        }
+    }
+
+    /// All sessions whose shard handles the guild.
+    pub fn sessions_for_guild(&self, guild_id: Id<GuildMarker>) -> Vec<Session> {
+        self.0
+            .lock()
+            .expect("Sessions mutex poisoned")
+            .values()
+            .filter(|session| shard_for(guild_id, session.shard_id))
+            .cloned()
+            .collect()
+    }
+
     pub fn destroy_session(&self, session_id: &SessionId) {
         self.0
             .lock()
@@ -61,19 +90,28 @@ impl Sessions {
 #[derive(Clone)]
 pub struct Session {
     /// Shard ID of the session.
-    shard_id: Option<ShardId>,
+    pub shard_id: Option<ShardId>,
     /// Compression as requested in IDENTIFY.
     compress: bool,
     /// Intents as requested in IDENTIFY.
-    intents: Intents,
+    pub intents: Intents,
+    /// Large threshold as requested in IDENTIFY.
+    pub large_threshold: u64,
+    /// Writer of the connection that identified or last resumed the session.
+    pub writer: WriteHandle,
+    /// Whether the session was resumed at least once.
+    pub resumed: bool,
 }
 
-impl From<&IdentifyInfo> for Session {
-    fn from(value: &IdentifyInfo) -> Self {
+impl Session {
+    fn new(identify: &IdentifyInfo, writer: WriteHandle) -> Self {
         Self {
-            shard_id: value.shard,
-            compress: value.compress,
-            intents: value.intents,
+            shard_id: identify.shard,
+            compress: identify.compress,
+            intents: identify.intents,
+            large_threshold: identify.large_threshold,
+            writer,
+            resumed: false,
         }
     }
 }
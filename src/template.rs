@@ -0,0 +1,241 @@
+use simd_json::{OwnedValue, StaticNode};
+use twilight_model::{
+    guild::Guild,
+    id::{
+        marker::{ChannelMarker, GenericMarker},
+        Id,
+    },
+};
+
+use crate::{
+    generator::{now, timestamp, Generator},
+    handler::ConnectionState,
+};
+
+/// Names of all placeholders.
+pub const PLACEHOLDERS: &[&str] = &[
+    "session_id",
+    "shard_id",
+    "seq",
+    "now",
+    "snowflake",
+    "random_guild.id",
+    "random_channel(guild).id",
+];
+
+/// A placeholder like `{{session_id}}` in a string of a dispatch payload.
+#[derive(Clone, Copy, Debug)]
+enum Placeholder {
+    SessionId,
+    ShardId,
+    Seq,
+    Now,
+    Snowflake,
+    RandomGuildId,
+    RandomChannelId,
+}
+
+impl Placeholder {
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "session_id" => Some(Self::SessionId),
+            "shard_id" => Some(Self::ShardId),
+            "seq" => Some(Self::Seq),
+            "now" => Some(Self::Now),
+            "snowflake" => Some(Self::Snowflake),
+            "random_guild.id" => Some(Self::RandomGuildId),
+            "random_channel(guild).id" => Some(Self::RandomChannelId),
+            _ => None,
+        }
+    }
+
+    /// A value of the right type, to check payloads without a connection.
+    fn example(self) -> OwnedValue {
+        match self {
+            Self::SessionId => OwnedValue::String("session".to_string()),
+            Self::ShardId | Self::Seq => 0.into(),
+            Self::Now => OwnedValue::String(timestamp(now()).iso_8601().to_string()),
+            Self::Snowflake | Self::RandomGuildId | Self::RandomChannelId => {
+                OwnedValue::String("1".to_string())
+            }
+        }
+    }
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(Placeholder),
+}
+
+/// Splits the string into text and placeholders. Returns the name of the
+/// first unknown placeholder as error.
+fn segments(mut input: &str) -> Result<Vec<Segment<'_>>, String> {
+    let mut segments = Vec::new();
+
+    while let Some(start) = input.find("{{") {
+        let Some(end) = input[start..].find("}}").map(|end| start + end) else {
+            break;
+        };
+
+        let name = &input[start + 2..end];
+        let placeholder = Placeholder::parse(name).ok_or_else(|| name.trim().to_string())?;
+
+        if start > 0 {
+            segments.push(Segment::Text(&input[..start]));
+        }
+
+        segments.push(Segment::Placeholder(placeholder));
+        input = &input[end + 2..];
+    }
+
+    if !input.is_empty() {
+        segments.push(Segment::Text(input));
+    }
+
+    Ok(segments)
+}
+
+/// Checks that all placeholders in the strings of the value are known.
+/// Returns the name of the first unknown placeholder as error.
+pub fn validate(value: &OwnedValue) -> Result<(), String> {
+    match value {
+        OwnedValue::String(string) => segments(string).map(|_| ()),
+        OwnedValue::Array(values) => values.iter().try_for_each(validate),
+        OwnedValue::Object(object) => object.values().try_for_each(validate),
+        OwnedValue::Static(_) => Ok(()),
+    }
+}
+
+/// Replaces the placeholders in the strings of the value.
+fn render(value: &OwnedValue, fill: &mut impl FnMut(Placeholder) -> OwnedValue) -> OwnedValue {
+    match value {
+        OwnedValue::String(string) => render_string(string, fill),
+        OwnedValue::Array(values) => {
+            OwnedValue::Array(values.iter().map(|value| render(value, fill)).collect())
+        }
+        OwnedValue::Object(object) => OwnedValue::Object(Box::new(
+            object
+                .iter()
+                .map(|(key, value)| (key.clone(), render(value, fill)))
+                .collect(),
+        )),
+        OwnedValue::Static(_) => value.clone(),
+    }
+}
+
+/// Strings that consist of a single placeholder are replaced by its value, so
+/// that numbers stay numbers. Otherwise the values are formatted into the
+/// string.
+fn render_string(string: &str, fill: &mut impl FnMut(Placeholder) -> OwnedValue) -> OwnedValue {
+    let Ok(segments) = segments(string) else {
+        return OwnedValue::String(string.to_string());
+    };
+
+    if let [Segment::Placeholder(placeholder)] = segments[..] {
+        return fill(placeholder);
+    }
+
+    let mut rendered = String::new();
+
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => rendered.push_str(text),
+            Segment::Placeholder(placeholder) => match fill(placeholder) {
+                OwnedValue::String(value) => rendered.push_str(&value),
+                OwnedValue::Static(StaticNode::Null) => {}
+                value => rendered.push_str(&value.to_string()),
+            },
+        }
+    }
+
+    OwnedValue::String(rendered)
+}
+
+/// The value with example values for its placeholders.
+pub fn example(value: &OwnedValue) -> OwnedValue {
+    render(value, &mut Placeholder::example)
+}
+
+/// Fills in the placeholders of one payload from the connection and the
+/// world. Random entities are picked once per payload, so that
+/// `{{random_channel(guild).id}}` is a channel of `{{random_guild.id}}`.
+pub struct Renderer<'a> {
+    state: &'a ConnectionState,
+    /// The generator of the script that sends the payload.
+    generator: &'a mut Generator,
+    guild: Option<Option<Guild>>,
+    channel: Option<Option<Id<ChannelMarker>>>,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(state: &'a ConnectionState, generator: &'a mut Generator) -> Self {
+        Self {
+            state,
+            generator,
+            guild: None,
+            channel: None,
+        }
+    }
+
+    pub fn render(&mut self, value: &OwnedValue) -> OwnedValue {
+        render(value, &mut |placeholder| self.value(placeholder))
+    }
+
+    fn value(&mut self, placeholder: Placeholder) -> OwnedValue {
+        match placeholder {
+            Placeholder::SessionId => self
+                .state
+                .session_id()
+                .map_or(OwnedValue::Static(StaticNode::Null), |id| {
+                    OwnedValue::String(id.clone())
+                }),
+            Placeholder::ShardId => self
+                .state
+                .session()
+                .and_then(|session| session.shard_id)
+                .map_or(OwnedValue::Static(StaticNode::Null), |shard| {
+                    shard.number().into()
+                }),
+            Placeholder::Seq => self.state.writer.next_sequence().into(),
+            Placeholder::Now => OwnedValue::String(timestamp(now()).iso_8601().to_string()),
+            Placeholder::Snowflake => {
+                OwnedValue::String(self.state.world.snowflake::<GenericMarker>().to_string())
+            }
+            Placeholder::RandomGuildId => self
+                .guild()
+                .map_or(OwnedValue::Static(StaticNode::Null), |guild| {
+                    OwnedValue::String(guild.id.to_string())
+                }),
+            Placeholder::RandomChannelId => self
+                .channel()
+                .map_or(OwnedValue::Static(StaticNode::Null), |id| {
+                    OwnedValue::String(id.to_string())
+                }),
+        }
+    }
+
+    fn guild(&mut self) -> Option<&Guild> {
+        let shard = self.state.session().and_then(|session| session.shard_id);
+        let world = &self.state.world;
+        let generator = &mut *self.generator;
+
+        self.guild
+            .get_or_insert_with(|| world.pick_guild(generator, shard))
+            .as_ref()
+    }
+
+    fn channel(&mut self) -> Option<Id<ChannelMarker>> {
+        if let Some(channel) = self.channel {
+            return channel;
+        }
+
+        let channel = self.guild().cloned().and_then(|guild| {
+            self.generator
+                .choose(&guild.channels)
+                .map(|channel| channel.id)
+        });
+        self.channel = Some(channel);
+
+        channel
+    }
+}
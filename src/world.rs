@@ -0,0 +1,520 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    process::exit,
+    sync::{Arc, Mutex},
+};
+
+use rand::{thread_rng, Rng};
+use serde::de::DeserializeSeed;
+use simd_json::OwnedValue;
+use tracing::{debug, info};
+use twilight_model::{
+    channel::{Channel, Message},
+    gateway::{
+        event::{DispatchEvent, DispatchEventWithTypeDeserializer},
+        presence::{Presence, Status},
+        ShardId,
+    },
+    guild::{Guild, PartialGuild},
+    id::{
+        marker::{ChannelMarker, GuildMarker},
+        Id,
+    },
+    voice::VoiceState,
+};
+
+use crate::{
+    config::{self, Config},
+    generator::{Generator, UserPool},
+};
+
+/// Amount of messages that are remembered per channel, so that replies and
+/// message updates can reference earlier messages.
+const MESSAGE_HISTORY: usize = 100;
+
+/// The mock world shared by all connections. Dispatches sent to clients are
+/// applied to it, so that later GUILD_CREATEs reflect them.
+#[derive(Clone)]
+pub struct World(Arc<Mutex<WorldState>>);
+
+struct WorldState {
+    guilds: Vec<Guild>,
+    /// Most recent messages per channel, oldest first.
+    messages: HashMap<Id<ChannelMarker>, VecDeque<Message>>,
+    /// Users that members of guilds created at runtime are drawn from.
+    pool: UserPool,
+    /// Generator for events created at runtime. It continues where the world
+    /// generation stopped, so runs with the same seed stay reproducible.
+    generator: Generator,
+}
+
+impl World {
+    pub fn new(guilds: Vec<Guild>, pool: UserPool, generator: Generator) -> Self {
+        Self(Arc::new(Mutex::new(WorldState {
+            guilds,
+            messages: HashMap::new(),
+            pool,
+            generator,
+        })))
+    }
+
+    /// Loads the fixture configured in the `mock_data` section of the config,
+    /// or generates the world if there is none.
+    pub fn from_config(config: &Config) -> Self {
+        let seed = config.mock_data.seed.unwrap_or_else(|| thread_rng().gen());
+        let mut generator = Generator::new(seed);
+
+        let Some(path) = &config.mock_data.fixture else {
+            info!("Generating world with seed {seed}");
+
+            let world = generator.world(config);
+
+            info!(
+                "Generated {} users and {} guilds",
+                world.pool.users.len(),
+                world.guilds.len()
+            );
+
+            return Self::new(world.guilds, world.pool, generator);
+        };
+
+        match config::load_fixture(path) {
+            Ok(fixture) => {
+                info!(
+                    "Loaded {} guilds from {path}, generating events with seed {seed}",
+                    fixture.guilds.len()
+                );
+
+                let pool = generator.user_pool(config);
+
+                Self::new(fixture.guilds, pool, generator)
+            }
+            Err(err) => {
+                // Avoid panicking
+                eprintln!("Fixture Error: {err}");
+                exit(1);
+            }
+        }
+    }
+
+    /// All guilds that are handled by the given shard, or all guilds if the
+    /// client did not identify with a shard.
+    pub fn guilds_for_shard(&self, shard: Option<ShardId>) -> Vec<Guild> {
+        self.0
+            .lock()
+            .expect("World mutex poisoned")
+            .guilds
+            .iter()
+            .filter(|guild| shard_for(guild.id, shard))
+            .cloned()
+            .collect()
+    }
+
+    /// The guild with the given ID if it is handled by the given shard.
+    pub fn guild_for_shard(
+        &self,
+        guild_id: Id<GuildMarker>,
+        shard: Option<ShardId>,
+    ) -> Option<Guild> {
+        if !shard_for(guild_id, shard) {
+            return None;
+        }
+
+        self.0
+            .lock()
+            .expect("World mutex poisoned")
+            .guilds
+            .iter()
+            .find(|guild| guild.id == guild_id)
+            .cloned()
+    }
+
+    /// A generator for the script of a session on the shard. Scripts draw
+    /// from their own generator, so that a seed reproduces their draws no
+    /// matter how connections and their tasks are scheduled.
+    pub fn script_generator(&self, shard: Option<ShardId>) -> Generator {
+        self.0
+            .lock()
+            .expect("World mutex poisoned")
+            .generator
+            .fork(shard.map_or(0, ShardId::number))
+    }
+
+    /// Creates a random message in one of the guilds handled by the shard and
+    /// adds it to the world.
+    pub fn random_message(
+        &self,
+        generator: &mut Generator,
+        shard: Option<ShardId>,
+    ) -> Option<Message> {
+        let mut state = self.0.lock().expect("World mutex poisoned");
+        let state = &mut *state;
+
+        let guilds: Vec<&Guild> = state
+            .guilds
+            .iter()
+            .filter(|guild| shard_for(guild.id, shard))
+            .collect();
+        let message = generator.message(&guilds, &state.messages)?;
+        state.add_message(&message);
+
+        Some(message)
+    }
+
+    /// Creates a random guild that the bot joins and adds it to the world.
+    pub fn random_guild(&self, generator: &mut Generator, config: &Config) -> Guild {
+        let mut state = self.0.lock().expect("World mutex poisoned");
+
+        let guild = generator.joined_guild(&state.pool, config);
+        state.guilds.push(guild.clone());
+
+        guild
+    }
+
+    /// A random guild handled by the shard.
+    pub fn pick_guild(&self, generator: &mut Generator, shard: Option<ShardId>) -> Option<Guild> {
+        let state = self.0.lock().expect("World mutex poisoned");
+
+        let guilds: Vec<&Guild> = state
+            .guilds
+            .iter()
+            .filter(|guild| shard_for(guild.id, shard))
+            .collect();
+
+        generator.choose(&guilds).map(|guild| (*guild).clone())
+    }
+
+    /// A snowflake for an entity that is created right now.
+    pub fn snowflake<T>(&self) -> Id<T> {
+        self.0
+            .lock()
+            .expect("World mutex poisoned")
+            .generator
+            .snowflake_now()
+    }
+
+    /// Applies a raw dispatch to the world. Events that twilight-model cannot
+    /// parse are sent as they are, but leave the world untouched.
+    pub fn apply_raw(&self, event_type: &str, data: &OwnedValue) {
+        match DispatchEventWithTypeDeserializer::new(event_type).deserialize(data) {
+            Ok(event) => self.apply(&event),
+            Err(e) => debug!("Not applying {event_type} to the world: {e}"),
+        }
+    }
+
+    pub fn apply(&self, event: &DispatchEvent) {
+        self.0.lock().expect("World mutex poisoned").apply(event);
+    }
+}
+
+/// Whether the guild is handled by the given shard. Clients without a shard
+/// receive all guilds.
+pub fn shard_for(guild_id: Id<GuildMarker>, shard: Option<ShardId>) -> bool {
+    match shard {
+        Some(shard) => (guild_id.get() >> 22) % shard.total() == shard.number(),
+        None => true,
+    }
+}
+
+/// Inserts the item or replaces the one with the same key.
+fn upsert<T, K: PartialEq>(items: &mut Vec<T>, item: T, key: impl Fn(&T) -> K) {
+    let item_key = key(&item);
+
+    match items.iter_mut().find(|existing| key(existing) == item_key) {
+        Some(existing) => *existing = item,
+        None => items.push(item),
+    }
+}
+
+impl WorldState {
+    fn guild_mut(&mut self, guild_id: Id<GuildMarker>) -> Option<&mut Guild> {
+        self.guilds.iter_mut().find(|guild| guild.id == guild_id)
+    }
+
+    fn apply(&mut self, event: &DispatchEvent) {
+        match event {
+            DispatchEvent::GuildCreate(guild) => {
+                upsert(&mut self.guilds, guild.0.clone(), |g| g.id)
+            }
+            DispatchEvent::GuildUpdate(update) => {
+                if let Some(guild) = self.guild_mut(update.id) {
+                    update_guild(guild, &update.0);
+                }
+            }
+            // Unavailable guilds are only down temporarily and come back
+            DispatchEvent::GuildDelete(delete) if !delete.unavailable => {
+                if let Some(guild) = self.guilds.iter().find(|guild| guild.id == delete.id) {
+                    for channel in &guild.channels {
+                        self.messages.remove(&channel.id);
+                    }
+                }
+
+                self.guilds.retain(|guild| guild.id != delete.id);
+            }
+            DispatchEvent::ChannelCreate(channel) => self.upsert_channel(&channel.0),
+            DispatchEvent::ChannelUpdate(channel) => self.upsert_channel(&channel.0),
+            DispatchEvent::ThreadCreate(thread) => self.upsert_channel(&thread.0),
+            DispatchEvent::ThreadUpdate(thread) => self.upsert_channel(&thread.0),
+            DispatchEvent::ChannelDelete(channel) => {
+                if let Some(guild_id) = channel.guild_id {
+                    self.remove_channel(guild_id, channel.id);
+                }
+            }
+            DispatchEvent::ThreadDelete(thread) => self.remove_channel(thread.guild_id, thread.id),
+            DispatchEvent::RoleCreate(create) => {
+                if let Some(guild) = self.guild_mut(create.guild_id) {
+                    upsert(&mut guild.roles, create.role.clone(), |role| role.id);
+                }
+            }
+            DispatchEvent::RoleUpdate(update) => {
+                if let Some(guild) = self.guild_mut(update.guild_id) {
+                    upsert(&mut guild.roles, update.role.clone(), |role| role.id);
+                }
+            }
+            DispatchEvent::RoleDelete(delete) => {
+                if let Some(guild) = self.guild_mut(delete.guild_id) {
+                    guild.roles.retain(|role| role.id != delete.role_id);
+
+                    for member in &mut guild.members {
+                        member.roles.retain(|role_id| *role_id != delete.role_id);
+                    }
+
+                    for channel in &mut guild.channels {
+                        if let Some(overwrites) = &mut channel.permission_overwrites {
+                            overwrites.retain(|overwrite| overwrite.id != delete.role_id.cast());
+                        }
+                    }
+                }
+            }
+            DispatchEvent::MemberAdd(add) => {
+                if let Some(guild) = self.guild_mut(add.guild_id) {
+                    upsert(&mut guild.members, add.member.clone(), |member| {
+                        member.user.id
+                    });
+                    guild.member_count = Some(guild.members.len() as u64);
+                }
+            }
+            DispatchEvent::MemberUpdate(update) => {
+                let Some(guild) = self.guild_mut(update.guild_id) else {
+                    return;
+                };
+
+                if let Some(member) = guild
+                    .members
+                    .iter_mut()
+                    .find(|member| member.user.id == update.user.id)
+                {
+                    member.avatar = update.avatar;
+                    member.communication_disabled_until = update.communication_disabled_until;
+                    member.deaf = update.deaf.unwrap_or(member.deaf);
+                    member.joined_at = update.joined_at;
+                    member.mute = update.mute.unwrap_or(member.mute);
+                    member.nick = update.nick.clone();
+                    member.pending = update.pending;
+                    member.premium_since = update.premium_since;
+                    member.roles = update.roles.clone();
+                    member.user = update.user.clone();
+                }
+            }
+            DispatchEvent::MemberRemove(remove) => {
+                if let Some(guild) = self.guild_mut(remove.guild_id) {
+                    let user_id = remove.user.id;
+
+                    guild.members.retain(|member| member.user.id != user_id);
+                    guild
+                        .presences
+                        .retain(|presence| presence.user.id() != user_id);
+                    guild
+                        .voice_states
+                        .retain(|voice_state| voice_state.user_id != user_id);
+                    guild.member_count = Some(guild.members.len() as u64);
+                }
+            }
+            DispatchEvent::PresenceUpdate(update) => self.update_presence(&update.0),
+            DispatchEvent::VoiceStateUpdate(update) => self.update_voice_state(&update.0),
+            DispatchEvent::GuildEmojisUpdate(update) => {
+                if let Some(guild) = self.guild_mut(update.guild_id) {
+                    guild.emojis = update.emojis.clone();
+                }
+            }
+            DispatchEvent::GuildStickersUpdate(update) => {
+                if let Some(guild) = self.guild_mut(update.guild_id) {
+                    guild.stickers = update.stickers.clone();
+                }
+            }
+            DispatchEvent::MessageCreate(message) => self.add_message(&message.0),
+            DispatchEvent::MessageUpdate(update) => {
+                let Some(message) = self
+                    .messages
+                    .get_mut(&update.channel_id)
+                    .and_then(|messages| messages.iter_mut().find(|m| m.id == update.id))
+                else {
+                    return;
+                };
+
+                if let Some(attachments) = &update.attachments {
+                    message.attachments = attachments.clone();
+                }
+
+                if let Some(content) = &update.content {
+                    message.content = content.clone();
+                }
+
+                if let Some(embeds) = &update.embeds {
+                    message.embeds = embeds.clone();
+                }
+
+                if let Some(mentions) = &update.mentions {
+                    message.mentions = mentions.clone();
+                }
+
+                if let Some(mention_roles) = &update.mention_roles {
+                    message.mention_roles = mention_roles.clone();
+                }
+
+                message.edited_timestamp = update.edited_timestamp.or(message.edited_timestamp);
+                message.mention_everyone =
+                    update.mention_everyone.unwrap_or(message.mention_everyone);
+                message.pinned = update.pinned.unwrap_or(message.pinned);
+            }
+            DispatchEvent::MessageDelete(delete) => {
+                if let Some(messages) = self.messages.get_mut(&delete.channel_id) {
+                    messages.retain(|message| message.id != delete.id);
+                }
+            }
+            DispatchEvent::MessageDeleteBulk(delete) => {
+                if let Some(messages) = self.messages.get_mut(&delete.channel_id) {
+                    messages.retain(|message| !delete.ids.contains(&message.id));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn upsert_channel(&mut self, channel: &Channel) {
+        let Some(guild) = channel
+            .guild_id
+            .and_then(|guild_id| self.guild_mut(guild_id))
+        else {
+            return;
+        };
+
+        if channel.kind.is_thread() {
+            upsert(&mut guild.threads, channel.clone(), |thread| thread.id);
+        } else {
+            upsert(&mut guild.channels, channel.clone(), |channel| channel.id);
+        }
+    }
+
+    fn remove_channel(&mut self, guild_id: Id<GuildMarker>, channel_id: Id<ChannelMarker>) {
+        if let Some(guild) = self.guild_mut(guild_id) {
+            guild.channels.retain(|channel| channel.id != channel_id);
+            guild.threads.retain(|thread| thread.id != channel_id);
+        }
+
+        self.messages.remove(&channel_id);
+    }
+
+    fn update_presence(&mut self, presence: &Presence) {
+        let Some(guild) = self.guild_mut(presence.guild_id) else {
+            return;
+        };
+
+        let user_id = presence.user.id();
+
+        // Offline members are not part of the presences
+        if presence.status == Status::Offline {
+            guild
+                .presences
+                .retain(|presence| presence.user.id() != user_id);
+        } else {
+            upsert(&mut guild.presences, presence.clone(), |presence| {
+                presence.user.id()
+            });
+        }
+    }
+
+    fn update_voice_state(&mut self, voice_state: &VoiceState) {
+        let Some(guild) = voice_state
+            .guild_id
+            .and_then(|guild_id| self.guild_mut(guild_id))
+        else {
+            return;
+        };
+
+        let user_id = voice_state.user_id;
+
+        if voice_state.channel_id.is_some() {
+            // Voice states in GUILD_CREATE omit the guild and member
+            let mut voice_state = voice_state.clone();
+            voice_state.guild_id = None;
+            voice_state.member = None;
+
+            upsert(&mut guild.voice_states, voice_state, |voice_state| {
+                voice_state.user_id
+            });
+        } else {
+            guild
+                .voice_states
+                .retain(|voice_state| voice_state.user_id != user_id);
+        }
+    }
+
+    fn add_message(&mut self, message: &Message) {
+        if let Some(guild) = message
+            .guild_id
+            .and_then(|guild_id| self.guild_mut(guild_id))
+        {
+            if let Some(channel) = guild
+                .channels
+                .iter_mut()
+                .chain(guild.threads.iter_mut())
+                .find(|channel| channel.id == message.channel_id)
+            {
+                channel.last_message_id = Some(message.id.cast());
+            }
+        }
+
+        let messages = self.messages.entry(message.channel_id).or_default();
+
+        if messages.len() >= MESSAGE_HISTORY {
+            messages.pop_front();
+        }
+
+        messages.push_back(message.clone());
+    }
+}
+
+fn update_guild(guild: &mut Guild, update: &PartialGuild) {
+    guild.afk_channel_id = update.afk_channel_id;
+    guild.afk_timeout = update.afk_timeout;
+    guild.application_id = update.application_id;
+    guild.banner = update.banner;
+    guild.default_message_notifications = update.default_message_notifications;
+    guild.description = update.description.clone();
+    guild.discovery_splash = update.discovery_splash;
+    guild.emojis = update.emojis.clone();
+    guild.explicit_content_filter = update.explicit_content_filter;
+    guild.features = update.features.clone();
+    guild.icon = update.icon;
+    guild.max_members = update.max_members;
+    guild.max_presences = update.max_presences;
+    guild.mfa_level = update.mfa_level;
+    guild.name = update.name.clone();
+    guild.nsfw_level = update.nsfw_level;
+    guild.owner_id = update.owner_id;
+    guild.preferred_locale = update.preferred_locale.clone();
+    guild.premium_progress_bar_enabled = update.premium_progress_bar_enabled;
+    guild.premium_subscription_count = update.premium_subscription_count;
+    guild.premium_tier = update.premium_tier;
+    guild.public_updates_channel_id = update.public_updates_channel_id;
+    guild.roles = update.roles.clone();
+    guild.rules_channel_id = update.rules_channel_id;
+    guild.splash = update.splash;
+    guild.system_channel_flags = update.system_channel_flags;
+    guild.system_channel_id = update.system_channel_id;
+    guild.vanity_url_code = update.vanity_url_code.clone();
+    guild.verification_level = update.verification_level;
+    guild.widget_channel_id = update.widget_channel_id;
+    guild.widget_enabled = update.widget_enabled;
+}